@@ -1,19 +1,28 @@
 use inline_python::{python, Context};
-use pyo3::{prelude::*, wrap_pyfunction};
+use pyo3::{prelude::*, wrap_pyfunction, wrap_pymodule};
 
 #[pyfunction]
 fn rust_print(x: i32) {
 	println!("rust: x = {}", x);
 }
 
+#[pymodule]
+fn rust_module(_py: Python, m: &PyModule) -> PyResult<()> {
+	m.add_function(wrap_pyfunction!(rust_print, m)?)
+}
+
 fn main() {
 	let c = Context::new();
 
 	c.add_wrapped(wrap_pyfunction!(rust_print));
+	c.add_module_as("rust.module", wrap_pymodule!(rust_module));
 
 	c.run(python! {
 		x = 123
 		print("python: x =", x)
 		rust_print(x)
+
+		from rust.module import rust_print as imported_print
+		imported_print(x + 1)
 	});
 }
@@ -0,0 +1,142 @@
+use crate::python_fn::split_commas;
+use proc_macro2::{Delimiter, Ident, Literal, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+fn error(span: Span, message: &str) -> TokenStream {
+	quote_spanned!(span => compile_error!{#message})
+}
+
+/// A field of a struct, and the name of its Python global variable.
+struct Field {
+	ident: Ident,
+	name: String,
+}
+
+/// Get the `name` from a `#[context(name = "..")]` attribute, if the attribute is one.
+fn context_attribute(attribute: TokenStream) -> Result<Option<String>, TokenStream> {
+	let tokens: Vec<TokenTree> = attribute.into_iter().collect();
+	let args = match &tokens[..] {
+		[TokenTree::Ident(i), TokenTree::Group(args)] if i == "context" && args.delimiter() == Delimiter::Parenthesis => args,
+		_ => return Ok(None),
+	};
+	let args: Vec<TokenTree> = args.stream().into_iter().collect();
+	match &args[..] {
+		[TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Literal(value)] if key == "name" && eq.as_char() == '=' => {
+			let value = value.to_string();
+			match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+				Some(name) => Ok(Some(name.to_string())),
+				None => Err(error(args[2].span(), "Expected a string literal")),
+			}
+		}
+		_ => Err(error(tokens[1].span(), "Expected `#[context(name = \"..\")]`")),
+	}
+}
+
+/// Skip the attributes and visibility at the start of `tokens`.
+///
+/// Returns the `name` of a `#[context(name = "..")]` attribute, if any.
+fn skip_attributes_and_visibility(
+	tokens: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>,
+) -> Result<Option<String>, TokenStream> {
+	let mut name = None;
+	while tokens.next_if(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == '#')).is_some() {
+		if let Some(TokenTree::Group(attribute)) = tokens.next() {
+			if let Some(n) = context_attribute(attribute.stream())? {
+				name = Some(n);
+			}
+		}
+	}
+	if tokens.next_if(|t| matches!(t, TokenTree::Ident(i) if i == "pub")).is_some() {
+		tokens.next_if(|t| matches!(t, TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis));
+	}
+	Ok(name)
+}
+
+/// Parse a struct with named fields.
+fn parse_struct(input: TokenStream, derive: &str) -> Result<(Ident, Vec<Field>), TokenStream> {
+	let mut tokens = input.into_iter().peekable();
+	skip_attributes_and_visibility(&mut tokens)?;
+
+	match tokens.next() {
+		Some(TokenTree::Ident(i)) if i == "struct" => {}
+		token => {
+			let message = format!("#[derive({})] is only supported on structs", derive);
+			return Err(error(token.map_or(Span::call_site(), |t| t.span()), &message));
+		}
+	}
+
+	let name = match tokens.next() {
+		Some(TokenTree::Ident(name)) => name,
+		_ => unreachable!(),
+	};
+
+	let body = match tokens.next() {
+		Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => body,
+		token => {
+			let message = format!("#[derive({})] is only supported on non-generic structs with named fields", derive);
+			return Err(error(token.map_or(name.span(), |t| t.span()), &message));
+		}
+	};
+
+	let fields = split_commas(body.stream())
+		.into_iter()
+		.map(|field| {
+			let mut tokens = field.into_iter().peekable();
+			let name = skip_attributes_and_visibility(&mut tokens)?;
+			match tokens.next() {
+				Some(TokenTree::Ident(ident)) => Ok(Field {
+					name: name.unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string()),
+					ident,
+				}),
+				token => Err(error(token.map_or(body.span(), |t| t.span()), "Expected a field name")),
+			}
+		})
+		.collect::<Result<_, _>>()?;
+
+	Ok((name, fields))
+}
+
+pub fn from_context_impl(input: TokenStream) -> Result<TokenStream, TokenStream> {
+	let (name, fields) = parse_struct(input, "FromContext")?;
+	let ident: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+	let varname = fields.iter().map(|f| Literal::string(&f.name));
+	// A hygienic name, so it can't conflict with the names of the fields.
+	let fields = Ident::new("fields", Span::mixed_site());
+	Ok(quote! {
+		impl ::inline_python::FromContext for #name {
+			fn from_context(
+				py: ::inline_python::pyo3::Python<'_>,
+				context: &::inline_python::Context,
+			) -> ::std::result::Result<Self, ::inline_python::InlinePythonError> {
+				let mut #fields = ::inline_python::ContextFields::new(py, context);
+				#(let #ident = #fields.get(#varname);)*
+				#fields.finish::<Self>()?;
+				Ok(Self {
+					#(#ident: #ident.unwrap(),)*
+				})
+			}
+		}
+	})
+}
+
+pub fn into_context_impl(input: TokenStream) -> Result<TokenStream, TokenStream> {
+	let (name, fields) = parse_struct(input, "IntoContext")?;
+	let ident = fields.iter().map(|f| &f.ident);
+	let varname = fields.iter().map(|f| Literal::string(&f.name));
+	Ok(quote! {
+		impl ::inline_python::IntoContext for #name {
+			fn into_context(
+				self,
+				py: ::inline_python::pyo3::Python<'_>,
+				context: &::inline_python::Context,
+			) -> ::std::result::Result<(), ::inline_python::InlinePythonError> {
+				let globals = context.globals(py);
+				(|| -> ::inline_python::pyo3::PyResult<()> {
+					#(globals.set_item(#varname, self.#ident)?;)*
+					Ok(())
+				})()
+				.map_err(|e| ::inline_python::InlinePythonError::new(py, e))
+			}
+		}
+	})
+}
@@ -1,13 +1,30 @@
 use proc_macro::Span;
-use proc_macro2::{Delimiter, Ident, Spacing, TokenStream, TokenTree};
-use quote::quote_spanned;
+use proc_macro2::{Delimiter, Group, Ident, Spacing, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
 use std::collections::BTreeMap;
 use std::fmt::Write;
+use std::iter::Peekable;
+
+/// A line and column in the Rust source file.
+type Position = (usize, usize);
+
+/// A `'buffer` or `'borrow` variable.
+pub struct Scoped {
+	pub var: Ident,
+	/// Whether this is a `'buffer` rather than a `'borrow`.
+	pub buffer: bool,
+	pub mutable: bool,
+}
 
 pub struct EmbedPython {
 	pub python: String,
-	pub variables: BTreeMap<String, Ident>,
+	pub variables: BTreeMap<String, TokenStream>,
+	pub expressions: usize,
+	pub write_back: BTreeMap<String, Ident>,
+	/// The variables that are shared with Python until the block is finished.
+	pub scoped: BTreeMap<String, Scoped>,
 	pub first_indent: Option<usize>,
+	pub first_line: Option<usize>,
 	pub line: usize,
 	pub column: usize,
 	pub compile_time: bool,
@@ -18,14 +35,33 @@ impl EmbedPython {
 		Self {
 			python: String::new(),
 			variables: BTreeMap::new(),
+			expressions: 0,
+			write_back: BTreeMap::new(),
+			scoped: BTreeMap::new(),
 			line: 1,
 			column: 0,
 			first_indent: None,
+			first_line: None,
 			compile_time: false,
 		}
 	}
 
+	/// The number of lines before the first line of Python code in the Rust source file.
+	pub fn line_offset(&self) -> usize {
+		self.first_line.map_or(0, |line| line - 1)
+	}
+
 	fn add_whitespace(&mut self, span: Span, line: usize, column: usize) -> Result<(), TokenStream> {
+		// The Python code starts at line 1, regardless of where it is in the
+		// Rust source file. The line numbers are shifted after compilation.
+		if self.first_line.is_none() {
+			self.first_line = Some(line);
+			self.first_indent = Some(column);
+			self.line = line;
+			self.column = column;
+			return Ok(());
+		}
+
 		#[allow(clippy::comparison_chain)]
 		if line > self.line {
 			while line > self.line {
@@ -49,8 +85,188 @@ impl EmbedPython {
 		Ok(())
 	}
 
+	/// Register the interpolated Rust variable or expression following a `'`.
+	///
+	/// Returns the name of the Python global variable for it, and the end of its span.
+	fn add_interpolation(&mut self, tokens: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<(String, Span), TokenStream> {
+		let name = if let Some(TokenTree::Ident(name)) = tokens.next() {
+			name
+		} else {
+			unreachable!()
+		};
+		if name == "_" {
+			// `'_(..)` or `'_{..}` interpolates an arbitrary Rust expression.
+			let expr = match tokens.next() {
+				Some(TokenTree::Group(g)) if matches!(g.delimiter(), Delimiter::Parenthesis | Delimiter::Brace) => g,
+				_ => {
+					return Err(quote_spanned!(name.span() => compile_error!{"Expected `(..)` or `{..}` after `'_`"}));
+				}
+			};
+			let name_str = format!("_RUST_{}", self.expressions);
+			self.expressions += 1;
+			let end_span = expr.span().unwrap().end();
+			let expr = match expr.delimiter() {
+				Delimiter::Parenthesis => Group::new(Delimiter::None, expr.stream()),
+				_ => expr,
+			};
+			self.variables.insert(name_str.clone(), TokenTree::Group(expr).into());
+			Ok((name_str, end_span))
+		} else if name == "mut" {
+			// `'mut var` is written back to the Rust variable after running the code.
+			let var = match tokens.next() {
+				Some(TokenTree::Ident(var)) => var,
+				_ => {
+					return Err(quote_spanned!(name.span() => compile_error!{"Expected a variable name after `'mut`"}));
+				}
+			};
+			let name_str = format!("_RUST_{}", var);
+			let end_span = var.span().unwrap().end();
+			self.variables.entry(name_str.clone()).or_insert_with(|| quote!(#var));
+			self.write_back.insert(name_str.clone(), var);
+			Ok((name_str, end_span))
+		} else if (name == "buffer" || name == "borrow") && matches!(tokens.peek(), Some(TokenTree::Ident(_))) {
+			// `'buffer var` shares the memory of a slice through the buffer protocol,
+			// and `'borrow var` lends a reference. `mut` makes them writable.
+			let mutable = tokens.next_if(|t| matches!(t, TokenTree::Ident(i) if i == "mut")).is_some();
+			let var = match tokens.next() {
+				Some(TokenTree::Ident(var)) => var,
+				_ => {
+					let message = format!("Expected a variable name after `'{} mut`", name);
+					return Err(quote_spanned!(name.span() => compile_error!{#message}));
+				}
+			};
+			let name_str = format!("_RUST_{}_{}", name, var);
+			let end_span = var.span().unwrap().end();
+			let buffer = name == "buffer";
+			self.scoped
+				.entry(name_str.clone())
+				.or_insert(Scoped {
+					var,
+					buffer,
+					mutable: false,
+				})
+				.mutable |= mutable;
+			Ok((name_str, end_span))
+		} else if name == "serde" && matches!(tokens.peek(), Some(TokenTree::Ident(_))) {
+			// `'serde var` is converted using its `serde::Serialize` implementation.
+			// A failure is returned by the `try_` functions that run the block.
+			let var = match tokens.next() {
+				Some(TokenTree::Ident(var)) => var,
+				_ => unreachable!(),
+			};
+			if !cfg!(feature = "serde") {
+				return Err(quote_spanned!(name.span() => compile_error!{"`'serde` requires the `serde` feature of inline-python"}));
+			}
+			let name_str = format!("_RUST_serde_{}", var);
+			let end_span = var.span().unwrap().end();
+			self.variables
+				.entry(name_str.clone())
+				.or_insert_with(|| quote!(::inline_python::to_python(globals.py(), &#var)?));
+			Ok((name_str, end_span))
+		} else {
+			let name_str = format!("_RUST_{}", name);
+			let end_span = name.span().unwrap().end();
+			self.variables.entry(name_str.clone()).or_insert_with(|| quote!(#name));
+			Ok((name_str, end_span))
+		}
+	}
+
+	/// Find all interpolations in the tokens, and their positions in the Rust source file.
+	fn find_interpolations(&mut self, input: TokenStream, result: &mut Vec<(Position, Position, String)>) -> Result<(), TokenStream> {
+		let mut tokens = input.into_iter().peekable();
+		while let Some(token) = tokens.next() {
+			match token {
+				TokenTree::Group(x) => self.find_interpolations(x.stream(), result)?,
+				TokenTree::Punct(x) if x.as_char() == '\'' && x.spacing() == Spacing::Joint => {
+					let start = x.span().unwrap();
+					let (name, end) = self.add_interpolation(&mut tokens)?;
+					result.push(((start.line(), start.column()), (end.line(), end.column()), name));
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	/// Add Python code using its original source text, rather than reconstructing it from the tokens.
+	///
+	/// This takes the source text of the macro invocation from the start of
+	/// the first token up to the closing delimiter. Only the interpolated
+	/// Rust variables and expressions are replaced.
+	pub fn add_verbatim(&mut self, input: TokenStream) -> Result<(), TokenStream> {
+		let first = match input.clone().into_iter().next() {
+			Some(token) => token.span().unwrap(),
+			None => return Ok(()),
+		};
+
+		let mut interpolations = Vec::new();
+		if !self.compile_time {
+			self.find_interpolations(input, &mut interpolations)?;
+		}
+		let mut interpolations = interpolations.into_iter().peekable();
+
+		// The source text of the whole macro invocation, without the closing delimiter.
+		// (Comments after the last token are not part of the tokens, but are part of the Python code.)
+		let call_site = Span::call_site();
+		let source = call_site
+			.source_text()
+			.and_then(|s| Some(s.strip_suffix(['}', ')', ']'])?.to_string()))
+			.ok_or_else(|| quote_spanned!(first.into() => compile_error!{"Unable to get the source text for #![verbatim]"}))?;
+
+		// Characters of each line before the column of the first token are
+		// removed, so the Python code starts without indentation.
+		let indent = first.column() - 1;
+		let mut skip_until = None;
+
+		for (line, text) in (call_site.line()..).zip(source.lines()) {
+			if line < first.line() {
+				continue;
+			}
+			if matches!(skip_until, Some((end_line, _)) if end_line < line) {
+				skip_until = None;
+			}
+			// The text of the first line starts at the column of the macro invocation.
+			let first_column = if line == call_site.line() { call_site.column() } else { 1 };
+			let start_column = if line == first.line() {
+				first.column()
+			} else if skip_until.is_some() {
+				1
+			} else {
+				self.python.push('\n');
+				let leading = text.chars().take_while(|c| c.is_whitespace()).count();
+				if leading >= indent {
+					indent + 1
+				} else if text.trim_start().is_empty() || text.trim_start().starts_with('#') {
+					leading + 1
+				} else {
+					return Err(quote_spanned!(first.into() => compile_error!{"Invalid indentation"}));
+				}
+			};
+			for (column, c) in (first_column..).zip(text.chars()) {
+				if let Some(end) = skip_until {
+					if (line, column) < end {
+						continue;
+					}
+					skip_until = None;
+				} else if column < start_column {
+					continue;
+				}
+				if let Some((_, end, name)) = interpolations.next_if(|(start, _, _)| *start <= (line, column)) {
+					self.python.push_str(&name);
+					skip_until = Some(end);
+					continue;
+				}
+				self.python.push(c);
+			}
+		}
+
+		self.first_line = Some(first.line());
+
+		Ok(())
+	}
+
 	pub fn add(&mut self, input: TokenStream) -> Result<(), TokenStream> {
-		let mut tokens = input.into_iter();
+		let mut tokens = input.into_iter().peekable();
 
 		while let Some(token) = tokens.next() {
 			let span = token.span().unwrap();
@@ -74,15 +290,10 @@ impl EmbedPython {
 				}
 				TokenTree::Punct(x) => {
 					if !self.compile_time && x.as_char() == '\'' && x.spacing() == Spacing::Joint {
-						let name = if let Some(TokenTree::Ident(name)) = tokens.next() {
-							name
-						} else {
-							unreachable!()
-						};
-						let name_str = format!("_RUST_{}", name);
-						self.python.push_str(&name_str);
-						self.column += name_str.chars().count() - 6 + 1;
-						self.variables.entry(name_str).or_insert(name);
+						let (name, end_span) = self.add_interpolation(&mut tokens)?;
+						self.python.push_str(&name);
+						self.line = end_span.line();
+						self.column = end_span.column();
 					} else if x.as_char() == '#' && x.spacing() == Spacing::Joint {
 						// Convert '##' to '//', because otherwise it's
 						// impossible to use the Python operators '//' and '//='.
@@ -1,10 +1,13 @@
 use proc_macro::Span;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span as Span2, TokenStream};
 use pyo3::{PyAny, PyErr, PyResult, PyTypeInfo, Python, ToPyObject};
 use quote::{quote, quote_spanned};
 
 /// Format a nice error message for a python compilation error.
-pub fn compile_error_msg(py: Python, error: PyErr, tokens: TokenStream) -> TokenStream {
+///
+/// `line_offset` is added to the line number of syntax errors, to map them
+/// from the line in the Python code to the line in the Rust source file.
+pub fn compile_error_msg(py: Python, error: PyErr, tokens: TokenStream, line_offset: usize) -> TokenStream {
 	let value = error.to_object(py);
 
 	if value.is_none(py) {
@@ -16,20 +19,20 @@ pub fn compile_error_msg(py: Python, error: PyErr, tokens: TokenStream) -> Token
 		let line: Option<usize> = value.getattr(py, "lineno").ok().and_then(|x| x.extract(py).ok());
 		let msg: Option<String> = value.getattr(py, "msg").ok().and_then(|x| x.extract(py).ok());
 		if let (Some(line), Some(msg)) = (line, msg) {
-			if let Some(span) = span_for_line(tokens.clone(), line) {
+			if let Some(span) = span_for_line(tokens.clone(), line + line_offset) {
 				let error = format!("python: {}", msg);
-				return quote_spanned!(span.into() => compile_error!{#error});
+				return quote_spanned!(span => compile_error!{#error});
 			}
 		}
 	}
 
 	if let Some(tb) = &error.traceback(py) {
 		if let Ok((file, line)) = get_traceback_info(tb) {
-			if file == Span::call_site().source_file().path().to_string_lossy() {
+			if file == Span::call_site().local_file().unwrap_or_default().to_string_lossy() {
 				if let Ok(msg) = value.as_ref(py).str() {
 					if let Some(span) = span_for_line(tokens, line) {
 						let error = format!("python: {}", msg);
-						return quote_spanned!(span.into() => compile_error!{#error});
+						return quote_spanned!(span => compile_error!{#error});
 					}
 				}
 			}
@@ -49,12 +52,15 @@ fn get_traceback_info(tb: &PyAny) -> PyResult<(String, usize)> {
 }
 
 /// Get a span for a specific line of input from a TokenStream.
-fn span_for_line(input: TokenStream, line: usize) -> Option<Span> {
+///
+/// Joining spans is only possible on nightly Rust. On stable Rust, this
+/// returns the span of the first token on the line.
+fn span_for_line(input: TokenStream, line: usize) -> Option<Span2> {
 	let mut spans = input
 		.into_iter()
-		.map(|x| x.span().unwrap())
-		.skip_while(|span| span.start().line() < line)
-		.take_while(|span| span.start().line() == line);
+		.map(|x| x.span())
+		.skip_while(|span| span.unwrap().line() < line)
+		.take_while(|span| span.unwrap().line() == line);
 
 	let mut result = spans.next()?;
 	for span in spans {
@@ -1,77 +1,230 @@
 //! Helper crate for `inline-python` and `ct-python`.
 
-#![feature(proc_macro_span)]
-
 extern crate proc_macro;
 
-use self::embed_python::EmbedPython;
+use self::embed_python::{EmbedPython, Scoped};
 use proc_macro::{Span, TokenStream as TokenStream1};
-use proc_macro2::{Literal, TokenStream};
-use pyo3::{ffi, types::PyBytes, AsPyPointer, FromPyPointer, PyObject, Python};
-use quote::quote;
+use proc_macro2::{Delimiter, Ident, Literal, Span as Span2, TokenStream, TokenTree};
+use pyo3::{
+	ffi,
+	types::{IntoPyDict, PyAny, PyBytes, PyTuple},
+	AsPyPointer, FromPyPointer, PyObject, PyResult, Python, ToPyObject,
+};
+use quote::{quote, quote_spanned};
 use std::ffi::CString;
 
+mod derive;
 mod embed_python;
 mod error;
+mod python_fn;
 mod run;
+mod transform;
 
 fn python_impl(input: TokenStream) -> Result<TokenStream, TokenStream> {
+	python_block(input, None)
+}
+
+/// Generate the code for a `python!{}` block.
+///
+/// For a `#[python_fn]` function, `arguments` are its arguments, which are
+/// passed to the Python code as parameters with the same name.
+fn python_block(input: TokenStream, arguments: Option<&[Ident]>) -> Result<TokenStream, TokenStream> {
 	let tokens = input.clone();
 
-	check_no_attribute(input.clone())?;
+	let verbatim = is_verbatim(input.clone());
+
+	if !verbatim {
+		check_no_attribute(input.clone())?;
+	}
 
-	let filename = Span::call_site().source_file().path().to_string_lossy().into_owned();
+	let filename = Span::call_site().local_file().unwrap_or_default().to_string_lossy().into_owned();
 
 	let mut x = EmbedPython::new();
 
-	x.add(input)?;
+	if verbatim {
+		x.add_verbatim(input)?;
+	} else {
+		x.add(input)?;
+	}
 
-	let EmbedPython { python, variables, .. } = x;
+	let line_offset = x.line_offset();
 
-	let python = CString::new(python).unwrap();
-	let filename = CString::new(filename).unwrap();
+	for argument in arguments.unwrap_or_default() {
+		x.variables.insert(argument.to_string(), quote!(&#argument));
+	}
+
+	let names = arguments.map(|arguments| arguments.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+	let parameters = names.as_ref().map(|names| names.iter().map(String::as_str).collect::<Vec<_>>());
+
+	let EmbedPython {
+		python,
+		mut variables,
+		write_back,
+		scoped,
+		..
+	} = x;
+
+	// Buffers and borrows are created when the variables are set, and are moved
+	// into the write back closure, such that they end when the block is finished.
+	// Mutable ones borrow their variable for the lifetime of the block, and move
+	// that reference into the closures, just like variables that are written back.
+	// Moving the reference into a local makes the closure capture it by value,
+	// such that the closure is `FnOnce` and the buffer or borrow can outlive it.
+	let scoped_ident: Vec<Ident> = scoped.keys().map(|name| Ident::new(name, Span2::mixed_site())).collect();
+	let scoped_ref: Vec<Ident> = scoped
+		.keys()
+		.map(|name| Ident::new(&name.replacen("_RUST_", "_RUST_ref_", 1), Span2::mixed_site()))
+		.collect();
+	let scoped_borrow = scoped
+		.values()
+		.zip(&scoped_ref)
+		.filter(|(s, _)| s.mutable)
+		.map(|(Scoped { var, buffer, .. }, reference)| {
+			if *buffer {
+				quote_spanned!(var.span() => let #reference = &mut #var[..];)
+			} else {
+				quote_spanned!(var.span() => let #reference = &mut #var;)
+			}
+		});
+	let scoped_new = scoped
+		.values()
+		.zip(&scoped_ref)
+		.map(|(Scoped { var, buffer, mutable }, reference)| match (buffer, mutable) {
+			(true, false) => quote_spanned!(var.span() => ::inline_python::SliceBuffer::new(&#var[..])),
+			(true, true) => quote_spanned!(var.span() => {
+				let #reference = #reference;
+				::inline_python::SliceBuffer::new_mut(#reference)
+			}),
+			(false, false) => quote_spanned!(var.span() => ::inline_python::Borrowed::new(&#var)),
+			(false, true) => quote_spanned!(var.span() => {
+				let #reference = #reference;
+				::inline_python::Borrowed::new_mut(#reference)
+			}),
+		});
+	for (name, ident) in scoped.keys().zip(&scoped_ident) {
+		variables.insert(name.clone(), quote!(&#ident));
+	}
 
-	let bytecode = unsafe {
-		let result: Result<Literal, TokenStream> = Python::with_gil(|py| {
-			let code = PyObject::from_owned_ptr_or_err(py, ffi::Py_CompileString(python.as_ptr(), filename.as_ptr(), ffi::Py_file_input))
-				.map_err(|err| error::compile_error_msg(py, err, tokens))?;
+	let (bytecode, magic, python_version, is_async) = unsafe {
+		let result: Result<_, TokenStream> = Python::with_gil(|py| {
+			let code = transform::compile_block(py, &python, &filename, parameters.as_deref())
+				.map_err(|err| error::compile_error_msg(py, err, tokens.clone(), line_offset))?;
 
-			Ok(Literal::byte_string(
+			let code = shift_line_numbers(code.as_ref(py), line_offset).map_err(|err| error::compile_error_msg(py, err, tokens, 0))?;
+
+			let bytecode = Literal::byte_string(
 				PyBytes::from_owned_ptr_or_err(py, ffi::PyMarshal_WriteObjectToString(code.as_ptr(), pyo3::marshal::VERSION))
 					.map_err(|_e| quote!(compile_error! {"failed to generate python bytecode"}))?
 					.as_bytes(),
-			))
+			);
+
+			// The magic number identifies the bytecode format, so the code can
+			// be compiled again at runtime if a different version of Python is used.
+			let magic = python_magic_number(py).map_err(|_e| quote!(compile_error! {"failed to get the python magic number"}))?;
+			let version = py.version_info();
+			let version = format!("{}.{}.{}", version.major, version.minor, version.patch);
+
+			// Code that uses `await` outside of a function can only be run as a coroutine.
+			let flags: std::os::raw::c_int = code
+				.getattr(py, "co_flags")
+				.and_then(|flags| flags.extract(py))
+				.map_err(|_e| quote!(compile_error! {"failed to get the flags of the python bytecode"}))?;
+
+			Ok((bytecode, Literal::byte_string(&magic), version, flags & ffi::CO_COROUTINE != 0))
 		});
 		result?
 	};
 
+	// The buffers and borrows would end when the future of `Context::run_async` is dropped,
+	// which safe code can skip by leaking the future, while Python still uses them.
+	if let (true, Some(Scoped { var, buffer, .. })) = (is_async, scoped.values().next()) {
+		let message = format!(
+			"`'{}` can not be used in a python!{{}} block that uses `await`",
+			if *buffer { "buffer" } else { "borrow" }
+		);
+		return Err(quote_spanned!(var.span() => compile_error!{#message}));
+	}
+
+	let line = line_offset + 1;
+
+	let parameters_tokens = match &names {
+		Some(names) => quote!(Some(&[#(#names),*])),
+		None => quote!(None),
+	};
+
+	// Variables that are written back are borrowed mutably for the lifetime of the block.
+	let reference = |var: &Ident| Ident::new(&format!("_RUST_ref_{}", var), Span2::mixed_site());
+	let write_back_ref = write_back.values().map(reference);
+	let write_back_var = write_back.values();
+	let write_back_extract = write_back.iter().map(|(name, var)| {
+		let reference = reference(var);
+		quote_spanned!(var.span() => *#reference = ::inline_python::write_back(globals, #name)?;)
+	});
+
 	let varname = variables.keys();
-	let var = variables.values();
+	let var = variables.iter().map(|(name, var)| match write_back.get(name) {
+		Some(var) => {
+			let reference = reference(var);
+			quote!(&*#reference)
+		}
+		None => var.clone(),
+	});
+
+	// Only `Context::run_async` accepts blocks that use `await`, which is checked by the type system.
+	let from_python_macro = if is_async {
+		quote!(::inline_python::FromAsyncInlinePython::from_python_macro)
+	} else {
+		quote!(::inline_python::FromInlinePython::from_python_macro)
+	};
 
 	Ok(quote! {
-		::inline_python::FromInlinePython::from_python_macro(
-			#bytecode,
-			|globals| {
-				#(
-					globals
-						.set_item(#varname, #var)
-						.expect("Unable to convert variable to Python");
-				)*
-			},
-		)
+		{
+			#(let #write_back_ref = &mut #write_back_var;)*
+			#(#scoped_borrow)*
+			#from_python_macro(
+				&::inline_python::EmbeddedCode {
+					bytecode: #bytecode,
+					magic: #magic,
+					python_version: #python_version,
+					source: #python,
+					filename: #filename,
+					line: #line,
+					parameters: #parameters_tokens,
+				},
+				|globals: &::inline_python::pyo3::types::PyDict| -> ::inline_python::pyo3::PyResult<_> {
+					#(let #scoped_ident = #scoped_new;)*
+					#(
+						#[allow(unused_braces)]
+						let value = #var;
+						globals.set_item(#varname, value)?;
+					)*
+					Ok(move |globals: &::inline_python::pyo3::types::PyDict| -> ::inline_python::pyo3::PyResult<()> {
+						#(#write_back_extract)*
+						#(::std::mem::drop(#scoped_ident);)*
+						Ok(())
+					})
+				},
+			)
+		}
 	})
 }
 
 fn ct_python_impl(input: TokenStream) -> Result<TokenStream, TokenStream> {
 	let tokens = input.clone();
 
-	let filename = Span::call_site().source_file().path().to_string_lossy().into_owned();
+	let filename = Span::call_site().local_file().unwrap_or_default().to_string_lossy().into_owned();
 
 	let mut x = EmbedPython::new();
 
 	x.compile_time = true;
 
-	x.add(input)?;
+	if is_verbatim(input.clone()) {
+		x.add_verbatim(input)?;
+	} else {
+		x.add(input)?;
+	}
+
+	let line_offset = x.line_offset();
 
 	let EmbedPython { python, .. } = x;
 
@@ -81,19 +234,35 @@ fn ct_python_impl(input: TokenStream) -> Result<TokenStream, TokenStream> {
 	Python::with_gil(|py| {
 		let code = unsafe {
 			PyObject::from_owned_ptr_or_err(py, ffi::Py_CompileString(python.as_ptr(), filename.as_ptr(), ffi::Py_file_input))
-				.map_err(|err| error::compile_error_msg(py, err, tokens.clone()))?
+				.map_err(|err| error::compile_error_msg(py, err, tokens.clone(), line_offset))?
 		};
 
+		let code = shift_line_numbers(code.as_ref(py), line_offset).map_err(|err| error::compile_error_msg(py, err, tokens.clone(), 0))?;
+
 		run::run_ct_python(py, code, tokens)
 	})
 }
 
+/// Check if the input starts with `#![verbatim]`.
+///
+/// In that case, the Python code is taken from the original source text
+/// instead of reconstructed from the tokens.
+fn is_verbatim(input: TokenStream) -> bool {
+	let mut input = input.into_iter();
+	input.next().is_some_and(|t| t.to_string() == "#")
+		&& input.next().is_some_and(|t| t.to_string() == "!")
+		&& input.next().is_some_and(|t| match t {
+			TokenTree::Group(g) => g.delimiter() == Delimiter::Bracket && g.stream().to_string() == "verbatim",
+			_ => false,
+		})
+}
+
 fn check_no_attribute(input: TokenStream) -> Result<(), TokenStream> {
 	let mut input = input.into_iter();
 	if let Some(token) = input.next() {
 		if token.to_string() == "#"
-			&& input.next().map_or(false, |t| t.to_string() == "!")
-			&& input.next().map_or(false, |t| t.to_string().starts_with('['))
+			&& input.next().is_some_and(|t| t.to_string() == "!")
+			&& input.next().is_some_and(|t| t.to_string().starts_with('['))
 		{
 			return Err(quote!(compile_error! {
 				"Attributes in python!{} are no longer supported. \
@@ -104,6 +273,42 @@ fn check_no_attribute(input: TokenStream) -> Result<(), TokenStream> {
 	Ok(())
 }
 
+/// The magic number (`importlib.util.MAGIC_NUMBER`) identifying the bytecode format of this version of Python.
+fn python_magic_number(py: Python) -> PyResult<Vec<u8>> {
+	let magic = py.import("importlib.util")?.getattr("MAGIC_NUMBER")?;
+	Ok(magic.downcast::<PyBytes>()?.as_bytes().to_vec())
+}
+
+/// Shift all line numbers in a code object by `offset`, including those of nested code objects.
+///
+/// The Python code is compiled starting at line 1, so this makes tracebacks,
+/// `warnings` and `inspect` refer to the right lines in the Rust source file.
+fn shift_line_numbers(code: &PyAny, offset: usize) -> PyResult<PyObject> {
+	let py = code.py();
+	if offset == 0 {
+		return Ok(code.into());
+	}
+	let code_type = code.get_type();
+	let consts = code
+		.getattr("co_consts")?
+		.iter()?
+		.map(|c| {
+			let c = c?;
+			if c.is_instance(code_type)? {
+				shift_line_numbers(c, offset)
+			} else {
+				Ok(c.into())
+			}
+		})
+		.collect::<PyResult<Vec<PyObject>>>()?;
+	let first_line: usize = code.getattr("co_firstlineno")?.extract()?;
+	let changes = [
+		("co_firstlineno", (first_line + offset).to_object(py)),
+		("co_consts", PyTuple::new(py, consts).to_object(py)),
+	];
+	Ok(code.call_method("replace", (), Some(changes.into_py_dict(py)))?.into())
+}
+
 #[doc(hidden)]
 #[proc_macro]
 pub fn python(input: TokenStream1) -> TokenStream1 {
@@ -113,6 +318,30 @@ pub fn python(input: TokenStream1) -> TokenStream1 {
 	})
 }
 
+#[proc_macro_attribute]
+pub fn python_fn(attr: TokenStream1, item: TokenStream1) -> TokenStream1 {
+	TokenStream1::from(match python_fn::python_fn_impl(TokenStream::from(attr), TokenStream::from(item)) {
+		Ok(tokens) => tokens,
+		Err(tokens) => tokens,
+	})
+}
+
+#[proc_macro_derive(FromContext, attributes(context))]
+pub fn from_context(input: TokenStream1) -> TokenStream1 {
+	TokenStream1::from(match derive::from_context_impl(TokenStream::from(input)) {
+		Ok(tokens) => tokens,
+		Err(tokens) => tokens,
+	})
+}
+
+#[proc_macro_derive(IntoContext, attributes(context))]
+pub fn into_context(input: TokenStream1) -> TokenStream1 {
+	TokenStream1::from(match derive::into_context_impl(TokenStream::from(input)) {
+		Ok(tokens) => tokens,
+		Err(tokens) => tokens,
+	})
+}
+
 #[doc(hidden)]
 #[proc_macro]
 pub fn ct_python(input: TokenStream1) -> TokenStream1 {
@@ -121,3 +350,12 @@ pub fn ct_python(input: TokenStream1) -> TokenStream1 {
 		Err(tokens) => tokens,
 	})
 }
+
+/// Expands to the Python code that compiles `python!{}` blocks, as a string literal.
+///
+/// The `inline-python` crate uses this to compile blocks again at runtime with the same code as the macro.
+#[doc(hidden)]
+#[proc_macro]
+pub fn transform_source(_input: TokenStream1) -> TokenStream1 {
+	TokenStream1::from(TokenStream::from(TokenTree::Literal(Literal::string(transform::TRANSFORM))))
+}
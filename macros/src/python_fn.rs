@@ -0,0 +1,162 @@
+use proc_macro2::{Delimiter, Group, Ident, Spacing, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+fn error(span: Span, message: &str) -> TokenStream {
+	quote_spanned!(span => compile_error!{#message})
+}
+
+/// Split tokens on the commas that are not inside `<..>`.
+pub fn split_commas(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+	let mut parts = vec![Vec::new()];
+	let mut depth = 0usize;
+	let mut previous_joint_minus = false;
+	for token in tokens {
+		if let TokenTree::Punct(p) = &token {
+			match p.as_char() {
+				',' if depth == 0 => {
+					parts.push(Vec::new());
+					previous_joint_minus = false;
+					continue;
+				}
+				'<' => depth += 1,
+				// The `>` of `->` does not close a `<`.
+				'>' if !previous_joint_minus => depth = depth.saturating_sub(1),
+				_ => {}
+			}
+			previous_joint_minus = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+		} else {
+			previous_joint_minus = false;
+		}
+		parts.last_mut().unwrap().push(token);
+	}
+	parts.retain(|part| !part.is_empty());
+	parts
+}
+
+/// Get the names of the arguments of a function, which must all be of the form `name: Type`.
+fn argument_names(arguments: &Group) -> Result<Vec<Ident>, TokenStream> {
+	split_commas(arguments.stream())
+		.into_iter()
+		.map(|argument| {
+			let mut tokens = argument.iter().peekable();
+			tokens.next_if(|t| matches!(t, TokenTree::Ident(i) if i == "mut"));
+			match (tokens.next(), tokens.next()) {
+				(Some(TokenTree::Ident(name)), _) if name == "self" => {
+					Err(error(name.span(), "#[python_fn] functions can not take `self`"))
+				}
+				(Some(TokenTree::Ident(name)), Some(TokenTree::Punct(colon))) if colon.as_char() == ':' => Ok(name.clone()),
+				(token, _) => Err(error(
+					token.map_or(arguments.span(), |t| t.span()),
+					"Expected an argument of the form `name: Type`",
+				)),
+			}
+		})
+		.collect()
+}
+
+/// Get the inner tokens of a body that only consists of a `python!{..}` invocation.
+fn python_macro_body(body: &Group) -> Option<Group> {
+	let tokens: Vec<TokenTree> = body.stream().into_iter().collect();
+	match &tokens[..] {
+		[TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(inner)]
+		| [TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(inner), TokenTree::Punct(_)]
+			if name == "python" && bang.as_char() == '!' =>
+		{
+			Some(inner.clone())
+		}
+		_ => None,
+	}
+}
+
+/// Generate a Rust function that runs its body as Python code.
+pub fn python_fn_impl(attr: TokenStream, item: TokenStream) -> Result<TokenStream, TokenStream> {
+	if let Some(token) = attr.into_iter().next() {
+		return Err(error(token.span(), "#[python_fn] does not take any arguments"));
+	}
+
+	let mut signature: Vec<TokenTree> = item.into_iter().collect();
+
+	let body = match signature.pop() {
+		Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => body,
+		token => {
+			return Err(error(
+				token.map_or(Span::call_site(), |t| t.span()),
+				"#[python_fn] can only be used on functions",
+			))
+		}
+	};
+
+	// The body must be valid Rust syntax, so most Python code has to be wrapped in `python!{}`.
+	let body = python_macro_body(&body).unwrap_or(body);
+
+	if crate::is_verbatim(body.stream()) {
+		return Err(error(body.span(), "#![verbatim] is not supported in #[python_fn] functions"));
+	}
+
+	let fn_index = signature
+		.iter()
+		.position(|t| matches!(t, TokenTree::Ident(i) if i == "fn"))
+		.ok_or_else(|| error(body.span(), "#[python_fn] can only be used on functions"))?;
+
+	if let Some(TokenTree::Ident(qualifier)) = signature[..fn_index]
+		.iter()
+		.find(|t| matches!(t, TokenTree::Ident(i) if i == "async"))
+	{
+		return Err(error(qualifier.span(), "#[python_fn] functions can not be async"));
+	}
+
+	// The arguments are the first parenthesized group after the name, outside of the generic parameters.
+	let mut depth = 0usize;
+	let mut arguments_index = None;
+	for (i, token) in signature.iter().enumerate().skip(fn_index + 1) {
+		match token {
+			TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+			TokenTree::Punct(p) if p.as_char() == '>' && !matches!(&signature[i - 1], TokenTree::Punct(m) if m.as_char() == '-') => {
+				depth = depth.saturating_sub(1)
+			}
+			TokenTree::Group(g) if depth == 0 && g.delimiter() == Delimiter::Parenthesis => {
+				arguments_index = Some(i);
+				break;
+			}
+			_ => {}
+		}
+	}
+	let arguments_index = arguments_index.ok_or_else(|| error(body.span(), "Expected function arguments"))?;
+	let arguments = match &signature[arguments_index] {
+		TokenTree::Group(g) => argument_names(g)?,
+		_ => unreachable!(),
+	};
+
+	// The return type is everything after `->`, up to an optional `where` clause.
+	let rest = &signature[arguments_index + 1..];
+	let return_type = match rest {
+		[TokenTree::Punct(minus), TokenTree::Punct(gt), return_type @ ..] if minus.as_char() == '-' && gt.as_char() == '>' => {
+			let end = return_type.iter().position(|t| matches!(t, TokenTree::Ident(i) if i == "where"));
+			Some(
+				return_type[..end.unwrap_or(return_type.len())]
+					.iter()
+					.cloned()
+					.collect::<TokenStream>(),
+			)
+		}
+		_ => None,
+	};
+
+	let block = crate::python_block(body.stream(), Some(&arguments))?;
+
+	let body = match return_type {
+		Some(return_type) => quote! {
+			let ::inline_python::Extract(result): ::inline_python::Extract<#return_type> = #block;
+			result
+		},
+		None => quote! {
+			let () = #block;
+		},
+	};
+
+	Ok(quote! {
+		#(#signature)* {
+			#body
+		}
+	})
+}
@@ -48,7 +48,7 @@ fn run_and_capture(py: Python, code: PyObject) -> PyResult<String> {
 }
 
 pub fn run_ct_python(py: Python, code: PyObject, tokens: TokenStream) -> Result<TokenStream, TokenStream> {
-	let output = run_and_capture(py, code).map_err(|err| compile_error_msg(py, err, tokens))?;
+	let output = run_and_capture(py, code).map_err(|err| compile_error_msg(py, err, tokens, 0))?;
 
 	// TokenStream::from_str emits any errors directly, so we don't need to do
 	// anything with the returned LexError.
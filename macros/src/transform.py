@@ -0,0 +1,74 @@
+import ast
+
+def _has_return(nodes):
+	for node in nodes:
+		if isinstance(node, ast.Return):
+			return True
+		if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef, ast.Lambda)):
+			continue
+		if _has_return(ast.iter_child_nodes(node)):
+			return True
+	return False
+
+def _has_await(nodes):
+	for node in nodes:
+		if isinstance(node, (ast.Await, ast.AsyncFor, ast.AsyncWith)):
+			return True
+		if isinstance(node, ast.comprehension) and node.is_async:
+			return True
+		if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef, ast.Lambda)):
+			continue
+		if _has_await(ast.iter_child_nodes(node)):
+			return True
+	return False
+
+def _at(node, location):
+	for n in ast.walk(node):
+		if 'lineno' in n._attributes:
+			ast.copy_location(n, location)
+
+def compile_block(source, filename, parameters=None):
+	module = ast.parse(source, filename)
+	body = module.body
+	if parameters is not None:
+		if not body:
+			body.append(ast.parse('pass').body[0])
+		elif isinstance(body[-1], ast.Expr):
+			value = ast.Return(value=body[-1].value)
+			ast.copy_location(value, body[-1])
+			body[-1] = value
+	if parameters is not None or _has_return(body):
+		parameters = ', '.join(parameters or [])
+		is_async = _has_await(body)
+		template = ast.parse(
+			'{1}def _RUST_block({0}):\n'
+			'	pass\n'
+			'try:\n'
+			'	_RUST_return = {2}_RUST_block({0})\n'
+			'finally:\n'
+			'	del _RUST_block\n'.format(parameters, 'async ' if is_async else '', 'await ' if is_async else '')
+		).body
+		for node in template:
+			_at(node, body[0])
+		names = sorted({n.id for n in ast.walk(module) if isinstance(n, ast.Name) and n.id.startswith('_RUST_')})
+		if names:
+			declaration = ast.Global(names=names)
+			ast.copy_location(declaration, body[0])
+			body.insert(0, declaration)
+		template[0].body = body
+		module.body = template
+	else:
+		_store_result(body)
+	return compile(module, filename, 'exec', flags=ast.PyCF_ALLOW_TOP_LEVEL_AWAIT, dont_inherit=True)
+
+def _store_result(body):
+	if body and isinstance(body[-1], ast.Expr):
+		assign = ast.parse('_RUST_return = None').body[0]
+		_at(assign, body[-1])
+		assign.value = body[-1].value
+		body[-1] = assign
+
+def compile_script(source, filename):
+	module = ast.parse(source, filename)
+	_store_result(module.body)
+	return compile(module, filename, 'exec', dont_inherit=True)
@@ -0,0 +1,30 @@
+use pyo3::{types::PyModule, PyObject, PyResult, Python};
+
+/// Python code to compile a `python!{}` block such that its result is stored in `_RUST_return`.
+///
+/// If the block contains a `return` statement outside of any function or
+/// class, the block is turned into the body of a function, and the return
+/// value of that function is the result.
+/// Otherwise, if the block ends in an expression, the value of that
+/// expression is the result.
+///
+/// If `parameters` are given, the block is always turned into the body of a
+/// function with those parameters, which is called with the global variables of
+/// the same names. The final expression is then returned from that function.
+///
+/// The block may use `await` outside of any function, which makes the
+/// resulting code object a coroutine. If it is turned into a function, that
+/// function is then an `async` function, which is awaited.
+///
+/// The `inline-python` crate uses the same code through [`transform_source!()`](crate::transform_source),
+/// to compile blocks again at runtime.
+pub const TRANSFORM: &str = include_str!("transform.py");
+
+/// Compile the Python code of a `python!{}` block, storing its result in `_RUST_return`.
+pub fn compile_block(py: Python, python: &str, filename: &str, parameters: Option<&[&str]>) -> PyResult<PyObject> {
+	let transform = PyModule::from_code(py, TRANSFORM, "<inline-python>", "_inline_python_transform")?;
+	Ok(transform
+		.getattr("compile_block")?
+		.call1((python, filename, parameters.map(<[&str]>::to_vec)))?
+		.into())
+}
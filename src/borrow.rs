@@ -0,0 +1,698 @@
+//! Lending Rust references to Python code, through a proxy object.
+
+use pyo3::{
+	exceptions::{PyAttributeError, PyReferenceError, PyRuntimeError, PyStopIteration, PyTypeError},
+	sync::GILOnceCell,
+	types::{PyAny, PyCFunction, PyDict, PyModule, PyTuple},
+	Py, PyObject, PyResult, Python, ToPyObject,
+};
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// A Rust type that Python code can use through a borrowed reference.
+///
+/// Python code accesses the value through a proxy object, which forwards
+/// attribute access to [`get_attr`](Borrowable::get_attr) and
+/// [`set_attr`](Borrowable::set_attr). Other attributes for which
+/// [`has_method`](Borrowable::has_method) returns `true` are methods, and calls,
+/// as in `value.name(..)`, are forwarded to [`call_method`](Borrowable::call_method),
+/// or [`call_method_mut`](Borrowable::call_method_mut) if the value is borrowed mutably.
+///
+/// If [`protocol`](Borrowable::protocol) returns [`Protocol::Sequence`] or
+/// [`Protocol::Mapping`], the proxy object is also a sequence or mapping,
+/// with the items provided by [`get_item`](Borrowable::get_item) and related methods.
+/// This is implemented for `Vec<T>`, `HashMap<K, V>` and `BTreeMap<K, V>`.
+///
+/// ```
+/// # use inline_python::{python, Borrowable};
+/// use inline_python::pyo3::{exceptions::PyAttributeError, prelude::*, types::{PyDict, PyTuple}};
+///
+/// struct Counter {
+///     count: u64,
+/// }
+///
+/// impl Borrowable for Counter {
+///     fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+///         match name {
+///             "count" => Ok(self.count.to_object(py)),
+///             _ => Err(PyAttributeError::new_err(name.to_string())),
+///         }
+///     }
+///
+///     fn has_method(&self, name: &str) -> bool {
+///         name == "add"
+///     }
+///
+///     fn call_method_mut(&mut self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+///         match name {
+///             "add" => {
+///                 let (n,): (u64,) = args.extract()?;
+///                 self.count += n;
+///                 Ok(py.None())
+///             }
+///             _ => self.call_method(py, name, args, kwargs),
+///         }
+///     }
+/// }
+///
+/// let mut counter = Counter { count: 1 };
+/// python! {
+///     'borrow mut counter.add(2)
+///     assert 'borrow mut counter.count == 3
+/// }
+/// assert_eq!(counter.count, 3);
+/// ```
+pub trait Borrowable {
+	/// Get the attribute `name` of the value.
+	///
+	/// Raise an `AttributeError` for unknown attributes, such that they can be methods.
+	fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject>;
+
+	/// Set the attribute `name` of the value.
+	///
+	/// This is only used if the value is borrowed mutably.
+	/// The default implementation raises an `AttributeError`.
+	fn set_attr(&mut self, py: Python<'_>, name: &str, value: &PyAny) -> PyResult<()> {
+		let _ = (py, value);
+		Err(no_attribute::<Self>(name))
+	}
+
+	/// Whether the value has a method `name`.
+	///
+	/// Only attributes for which this returns `true` can be called, through
+	/// [`call_method`](Borrowable::call_method) or [`call_method_mut`](Borrowable::call_method_mut).
+	/// Other attributes that [`get_attr`](Borrowable::get_attr) doesn't provide
+	/// raise an `AttributeError`, such that `hasattr(..)` works as expected.
+	/// Special methods, such as `__len__`, are never forwarded.
+	///
+	/// The default implementation returns `false`.
+	fn has_method(&self, name: &str) -> bool {
+		let _ = name;
+		false
+	}
+
+	/// Call the method `name` of the value.
+	///
+	/// The default implementation raises an `AttributeError`.
+	fn call_method(&self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		let _ = (py, args, kwargs);
+		Err(no_attribute::<Self>(name))
+	}
+
+	/// Call the method `name` of a mutably borrowed value.
+	///
+	/// The default implementation calls [`call_method`](Borrowable::call_method).
+	fn call_method_mut(&mut self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		self.call_method(py, name, args, kwargs)
+	}
+
+	/// The protocol of the proxy object.
+	///
+	/// The default is [`Protocol::Object`].
+	fn protocol(&self) -> Protocol {
+		Protocol::Object
+	}
+
+	/// The number of items, for `len(..)`.
+	fn length(&self) -> PyResult<usize> {
+		Err(PyTypeError::new_err(format!("`{}` has no len()", std::any::type_name::<Self>())))
+	}
+
+	/// Get an item, for `value[key]`.
+	///
+	/// For a sequence, `key` is an integer that can be negative, or a slice.
+	/// An `IndexError` or `KeyError` indicates that the item doesn't exist.
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		let _ = (py, key);
+		Err(PyTypeError::new_err(format!(
+			"`{}` is not subscriptable",
+			std::any::type_name::<Self>()
+		)))
+	}
+
+	/// Set an item, for `value[key] = item`.
+	fn set_item(&mut self, py: Python<'_>, key: &PyAny, item: &PyAny) -> PyResult<()> {
+		let _ = (py, key, item);
+		Err(PyTypeError::new_err(format!(
+			"`{}` does not support item assignment",
+			std::any::type_name::<Self>()
+		)))
+	}
+
+	/// Delete an item, for `del value[key]`.
+	fn del_item(&mut self, py: Python<'_>, key: &PyAny) -> PyResult<()> {
+		let _ = (py, key);
+		Err(PyTypeError::new_err(format!(
+			"`{}` does not support item deletion",
+			std::any::type_name::<Self>()
+		)))
+	}
+
+	/// Insert an item into a sequence before `index`, which is at most [`length`](Borrowable::length).
+	///
+	/// This is used by `insert`, `append` and `extend`.
+	fn insert_item(&mut self, py: Python<'_>, index: usize, item: &PyAny) -> PyResult<()> {
+		let _ = (py, index, item);
+		Err(PyTypeError::new_err(format!(
+			"`{}` does not support item insertion",
+			std::any::type_name::<Self>()
+		)))
+	}
+
+	/// The keys of a mapping, in iteration order.
+	///
+	/// The proxy iterates over them lazily, and converts every key to Python when it is reached.
+	/// Like for a `dict`, iteration fails if the mapping is changed in the meantime.
+	fn keys(&self) -> PyResult<Box<dyn Iterator<Item = &dyn ToPyObject> + '_>> {
+		Err(PyTypeError::new_err(format!("`{}` is not iterable", std::any::type_name::<Self>())))
+	}
+}
+
+/// The protocol of the proxy object of a [`Borrowable`] value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Protocol {
+	/// Only attributes and methods.
+	Object,
+	/// A `collections.abc.MutableSequence`, with items by index.
+	Sequence,
+	/// A `collections.abc.MutableMapping`, which iterates over its keys.
+	Mapping,
+}
+
+pub(crate) fn no_attribute<T: ?Sized>(name: &str) -> pyo3::PyErr {
+	PyAttributeError::new_err(format!("`{}` has no attribute '{}'", std::any::type_name::<T>(), name))
+}
+
+impl<T: Borrowable + ?Sized> Borrowable for &T {
+	fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		(**self).get_attr(py, name)
+	}
+
+	fn has_method(&self, name: &str) -> bool {
+		(**self).has_method(name)
+	}
+
+	fn call_method(&self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		(**self).call_method(py, name, args, kwargs)
+	}
+
+	fn protocol(&self) -> Protocol {
+		(**self).protocol()
+	}
+
+	fn length(&self) -> PyResult<usize> {
+		(**self).length()
+	}
+
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		(**self).get_item(py, key)
+	}
+
+	fn keys(&self) -> PyResult<Box<dyn Iterator<Item = &dyn ToPyObject> + '_>> {
+		(**self).keys()
+	}
+}
+
+impl<T: Borrowable + ?Sized> Borrowable for &mut T {
+	fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		(**self).get_attr(py, name)
+	}
+
+	fn set_attr(&mut self, py: Python<'_>, name: &str, value: &PyAny) -> PyResult<()> {
+		(**self).set_attr(py, name, value)
+	}
+
+	fn has_method(&self, name: &str) -> bool {
+		(**self).has_method(name)
+	}
+
+	fn call_method(&self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		(**self).call_method(py, name, args, kwargs)
+	}
+
+	fn call_method_mut(&mut self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		(**self).call_method_mut(py, name, args, kwargs)
+	}
+
+	fn protocol(&self) -> Protocol {
+		(**self).protocol()
+	}
+
+	fn length(&self) -> PyResult<usize> {
+		(**self).length()
+	}
+
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		(**self).get_item(py, key)
+	}
+
+	fn set_item(&mut self, py: Python<'_>, key: &PyAny, item: &PyAny) -> PyResult<()> {
+		(**self).set_item(py, key, item)
+	}
+
+	fn del_item(&mut self, py: Python<'_>, key: &PyAny) -> PyResult<()> {
+		(**self).del_item(py, key)
+	}
+
+	fn insert_item(&mut self, py: Python<'_>, index: usize, item: &PyAny) -> PyResult<()> {
+		(**self).insert_item(py, index, item)
+	}
+
+	fn keys(&self) -> PyResult<Box<dyn Iterator<Item = &dyn ToPyObject> + '_>> {
+		(**self).keys()
+	}
+}
+
+/// A Rust reference that is lent to Python code, as a proxy object.
+///
+/// Within a `python!{}` block, use `'borrow var` to lend `&var`, or
+/// `'borrow mut var` to lend `&mut var`, for the duration of the block.
+/// The type needs to implement [`Borrowable`].
+///
+/// To lend a reference to Python code in a [`Context`](crate::Context),
+/// use [`Borrowed::scope`] or [`Borrowed::scope_mut`].
+///
+/// When the block or scope ends, the proxy stops working.
+/// Using it after that, for example through a global variable in the context,
+/// raises a `ReferenceError`.
+///
+/// Python code can use the proxy from any thread, so lending a shared reference
+/// requires the type to be [`Sync`], and lending a mutable reference requires it
+/// to be [`Send`]. A mutably borrowed value is only used by one thread at a time.
+pub struct Borrowed<'a> {
+	proxy: PyObject,
+	lent: Arc<Lent>,
+	borrow: PhantomData<&'a mut ()>,
+}
+
+impl<'a> Borrowed<'a> {
+	/// Lend a shared reference to Python, for the duration of `f`.
+	///
+	/// ```
+	/// # use inline_python::{python, Borrowable, Borrowed, Context};
+	/// # use inline_python::pyo3::prelude::*;
+	/// struct Config {
+	///     name: String,
+	/// }
+	///
+	/// impl Borrowable for Config {
+	///     fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+	///         match name {
+	///             "name" => Ok(self.name.to_object(py)),
+	///             _ => Err(pyo3::exceptions::PyAttributeError::new_err(name.to_string())),
+	///         }
+	///     }
+	/// }
+	///
+	/// let c = Context::new();
+	/// let config = Config { name: "test".into() };
+	/// Borrowed::scope(&config, |config| {
+	///     c.set("config", config);
+	///     c.run(python! {
+	///         name = config.name
+	///     });
+	/// });
+	/// assert_eq!(c.get::<String>("name"), "test");
+	/// ```
+	pub fn scope<T: Borrowable + Sync, R>(value: &T, f: impl FnOnce(&Borrowed) -> R) -> R {
+		f(&Borrowed::new(value))
+	}
+
+	/// Lend a mutable reference to Python, for the duration of `f`.
+	pub fn scope_mut<T: Borrowable + Send, R>(value: &mut T, f: impl FnOnce(&Borrowed) -> R) -> R {
+		f(&Borrowed::new_mut(value))
+	}
+
+	/// Lend a shared reference. Used by `'borrow var`.
+	///
+	/// The proxy must be dropped before the borrow ends, since Python code can use the value until then.
+	/// This is not a public API: the `python!{}` macro moves it into the closures of the block,
+	/// which the crate drops at the end of the block, and [`Borrowed::scope`] drops it when `f` returns.
+	/// Use [`Borrowed::scope`] instead.
+	#[doc(hidden)]
+	pub fn new<T: Borrowable + Sync + 'a>(value: &'a T) -> Self {
+		let value = NonNull::from(value as &(dyn Borrowable + 'a));
+		// Safety: `value` is valid for `'a`, and the result is dropped before `'a` ends, as described above.
+		unsafe { Self::from_raw(value, std::any::type_name::<T>(), false) }
+	}
+
+	/// Lend a mutable reference. Used by `'borrow mut var`.
+	///
+	/// See [`Borrowed::new`]. Use [`Borrowed::scope_mut`] instead.
+	#[doc(hidden)]
+	pub fn new_mut<T: Borrowable + Send + 'a>(value: &'a mut T) -> Self {
+		let value = NonNull::from(value as &mut (dyn Borrowable + 'a));
+		// Safety: see `Borrowed::new`.
+		unsafe { Self::from_raw(value, std::any::type_name::<T>(), true) }
+	}
+
+	/// # Safety
+	///
+	/// `value` must be valid for `'a`, and the result must be dropped before `'a` ends.
+	unsafe fn from_raw(value: NonNull<dyn Borrowable + 'a>, type_name: &'static str, mutable: bool) -> Self {
+		let lent = Arc::new(Lent {
+			// The lifetime is erased. The value is removed when the borrow ends.
+			value: Cell::new(Some(std::mem::transmute::<NonNull<dyn Borrowable + 'a>, NonNull<dyn Borrowable>>(
+				value,
+			))),
+			type_name,
+			mutable,
+			uses: Cell::new(0),
+			changes: Cell::new(0),
+		});
+		Python::with_gil(|py| {
+			let proxy = new_proxy(py, lent.clone(), value.as_ref().protocol()).unwrap_or_else(|e| {
+				e.print(py);
+				panic!("Unable to lend a `{}` to Python", type_name);
+			});
+			Self {
+				proxy,
+				lent,
+				borrow: PhantomData,
+			}
+		})
+	}
+}
+
+impl ToPyObject for Borrowed<'_> {
+	fn to_object(&self, py: Python) -> PyObject {
+		self.proxy.clone_ref(py)
+	}
+}
+
+impl Drop for Borrowed<'_> {
+	fn drop(&mut self) {
+		Python::with_gil(|py| {
+			// Another thread might be using the value with the GIL released, such as
+			// in a method that calls `allow_threads`. The borrow can only end after that.
+			while self.lent.uses.get() != 0 {
+				py.allow_threads(std::thread::yield_now);
+			}
+			self.lent.value.set(None);
+		});
+	}
+}
+
+/// The state shared between a [`Borrowed`] and its proxy object.
+struct Lent {
+	/// The borrowed value, until the borrow ends.
+	value: Cell<Option<NonNull<dyn Borrowable>>>,
+	type_name: &'static str,
+	mutable: bool,
+	/// The number of shared uses in progress, or -1 during a mutable use.
+	uses: Cell<isize>,
+	/// The number of changes to the value so far, which invalidate its iterators.
+	changes: Cell<u64>,
+}
+
+// Safety: the fields are only used while holding the GIL. The value itself is
+// used from any thread that calls the proxy, which is why a shared borrow
+// requires `Sync`, and a mutable borrow requires `Send` and is only used by one
+// thread at a time.
+unsafe impl Send for Lent {}
+unsafe impl Sync for Lent {}
+
+/// Marks the value as in use until dropped.
+struct Use<'l> {
+	lent: &'l Lent,
+	mutable: bool,
+}
+
+impl<'l> Use<'l> {
+	fn new(lent: &'l Lent, mutable: bool) -> PyResult<Self> {
+		let uses = lent.uses.get();
+		if uses < 0 || (mutable && uses > 0) {
+			return Err(PyRuntimeError::new_err(format!(
+				"the borrowed `{}` is already in use",
+				lent.type_name
+			)));
+		}
+		lent.uses.set(if mutable { -1 } else { uses + 1 });
+		Ok(Self { lent, mutable })
+	}
+}
+
+impl Drop for Use<'_> {
+	fn drop(&mut self) {
+		let uses = self.lent.uses.get();
+		self.lent.uses.set(if self.mutable { 0 } else { uses - 1 });
+	}
+}
+
+impl Lent {
+	/// The borrowed value, or a `ReferenceError` if the borrow has ended.
+	fn value(&self) -> PyResult<*mut dyn Borrowable> {
+		let value = self.value.get().ok_or_else(|| {
+			PyReferenceError::new_err(format!(
+				"the borrowed `{}` is no longer available, since the borrow has ended",
+				self.type_name
+			))
+		})?;
+		Ok(value.as_ptr())
+	}
+
+	/// Perform the operation `op` on the borrowed value.
+	///
+	/// The attribute operations are `"get"`, `"set"`, `"has"` and `"call"`, with an attribute name as `key`.
+	/// The item operations are `"len"`, `"getitem"`, `"setitem"`, `"delitem"`, `"insert"` and `"keys"`.
+	fn access(self: &Arc<Self>, py: Python<'_>, op: &str, key: &PyAny, arg: &PyAny) -> PyResult<PyObject> {
+		let value = self.value()?;
+		let mutating = matches!(op, "set" | "setitem" | "delitem" | "insert");
+		if mutating && !self.mutable {
+			let error = format!("the `{}` is not borrowed mutably", self.type_name);
+			return Err(match op {
+				"set" => PyAttributeError::new_err(format!("can't set attribute '{}', since {}", key, error)),
+				_ => PyTypeError::new_err(format!("can't modify items, since {}", error)),
+			});
+		}
+		if mutating || (op == "call" && self.mutable) {
+			self.changes.set(self.changes.get() + 1);
+		}
+		// Safety: the value is still borrowed, and `Use` prevents conflicting references.
+		// Every use of a mutable borrow is exclusive, since the value might not be `Sync`.
+		unsafe {
+			let _use = Use::new(self, self.mutable)?;
+			match op {
+				"get" => (*value).get_attr(py, key.extract()?),
+				"set" => {
+					(*value).set_attr(py, key.extract()?, arg)?;
+					Ok(py.None())
+				}
+				"has" => Ok((*value).has_method(key.extract()?).to_object(py)),
+				"call" => {
+					let (args, kwargs): (&PyTuple, Option<&PyDict>) = arg.extract()?;
+					if self.mutable {
+						(*value).call_method_mut(py, key.extract()?, args, kwargs)
+					} else {
+						(*value).call_method(py, key.extract()?, args, kwargs)
+					}
+				}
+				"len" => Ok((*value).length()?.to_object(py)),
+				"getitem" => (*value).get_item(py, key),
+				"setitem" => {
+					(*value).set_item(py, key, arg)?;
+					Ok(py.None())
+				}
+				"delitem" => {
+					(*value).del_item(py, key)?;
+					Ok(py.None())
+				}
+				"insert" => {
+					// Like `list.insert`, a negative index counts from the end, and the index is clamped.
+					let len = (*value).length()? as isize;
+					let index: isize = key.extract()?;
+					let index = if index < 0 { index + len } else { index };
+					(*value).insert_item(py, index.clamp(0, len) as usize, arg)?;
+					Ok(py.None())
+				}
+				_ => {
+					let keys = (*value).keys()?;
+					// Safety: the lifetime is erased. `Keys` only uses the iterator while the value is still borrowed and unchanged.
+					let keys = std::mem::transmute::<
+						Box<dyn Iterator<Item = &dyn ToPyObject> + '_>,
+						Box<dyn Iterator<Item = &'static dyn ToPyObject>>,
+					>(keys);
+					new_keys(
+						py,
+						Keys {
+							lent: self.clone(),
+							changes: self.changes.get(),
+							keys: Some(keys),
+						},
+					)
+				}
+			}
+		}
+	}
+}
+
+/// An iterator over the keys of a borrowed mapping, as used by its proxy.
+struct Keys {
+	lent: Arc<Lent>,
+	/// The number of changes to the value when the iterator was created.
+	changes: u64,
+	/// The iterator, until it is exhausted.
+	keys: Option<Box<dyn Iterator<Item = &'static dyn ToPyObject>>>,
+}
+
+// Safety: the iterator is only used while holding the GIL, under the same conditions as the value itself.
+unsafe impl Send for Keys {}
+
+impl Keys {
+	/// Whether the iterator can still be used: the value is still borrowed, and was not changed.
+	fn is_valid(&self) -> bool {
+		self.lent.value.get().is_some() && self.lent.changes.get() == self.changes
+	}
+
+	fn next(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+		self.lent.value()?;
+		if self.lent.changes.get() != self.changes {
+			return Err(PyRuntimeError::new_err(format!(
+				"the borrowed `{}` changed during iteration",
+				self.lent.type_name
+			)));
+		}
+		let _use = Use::new(&self.lent, self.lent.mutable)?;
+		let key = self.keys.as_mut().and_then(|keys| keys.next()).map(|key| key.to_object(py));
+		if key.is_none() {
+			self.keys = None;
+		}
+		Ok(key)
+	}
+}
+
+impl Drop for Keys {
+	fn drop(&mut self) {
+		// The iterator might refer to the value, which it can no longer use after the borrow ended or the value changed.
+		if !self.is_valid() {
+			std::mem::forget(self.keys.take());
+		}
+	}
+}
+
+/// The Python class of the proxy objects.
+const PROXY: &str = r#"
+import collections.abc
+
+class Borrowed:
+	__slots__ = ("_access", "_type")
+
+	def __init__(self, access, type):
+		object.__setattr__(self, "_access", access)
+		object.__setattr__(self, "_type", type)
+
+	def __getattr__(self, name):
+		access = object.__getattribute__(self, "_access")
+		try:
+			return access("get", name, None)
+		except AttributeError:
+			# Special methods are looked up on the type, so they are never forwarded.
+			if name.startswith("__") and name.endswith("__") or not access("has", name, None):
+				raise
+		def method(*args, **kwargs):
+			return access("call", name, (args, kwargs or None))
+		method.__name__ = name
+		return method
+
+	def __setattr__(self, name, value):
+		object.__getattribute__(self, "_access")("set", name, value)
+
+	def __repr__(self):
+		return f"<borrowed {object.__getattribute__(self, '_type')}>"
+
+class BorrowedSequence(Borrowed, collections.abc.MutableSequence):
+	__slots__ = ()
+
+	def __len__(self):
+		return self._access("len", None, None)
+
+	def __getitem__(self, index):
+		return self._access("getitem", index, None)
+
+	def __setitem__(self, index, value):
+		self._access("setitem", index, value)
+
+	def __delitem__(self, index):
+		self._access("delitem", index, None)
+
+	def insert(self, index, value):
+		self._access("insert", index, value)
+
+class BorrowedMapping(Borrowed, collections.abc.MutableMapping):
+	__slots__ = ()
+
+	def __len__(self):
+		return self._access("len", None, None)
+
+	def __getitem__(self, key):
+		return self._access("getitem", key, None)
+
+	def __setitem__(self, key, value):
+		self._access("setitem", key, value)
+
+	def __delitem__(self, key):
+		self._access("delitem", key, None)
+
+	def __iter__(self):
+		return self._access("keys", None, None)
+
+class BorrowedKeys:
+	__slots__ = ("_next",)
+
+	def __init__(self, next):
+		self._next = next
+
+	def __iter__(self):
+		return self
+
+	def __next__(self):
+		return self._next()
+"#;
+
+fn proxy_module(py: Python<'_>) -> PyResult<&PyModule> {
+	static PROXY_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+	PROXY_MODULE
+		.get_or_try_init(py, || -> PyResult<_> {
+			Ok(PyModule::from_code(py, PROXY, "<inline-python>", "_inline_python_borrow")?.into())
+		})
+		.map(|module| module.as_ref(py))
+}
+
+/// Create the Python iterator over the keys of a borrowed mapping.
+fn new_keys(py: Python<'_>, keys: Keys) -> PyResult<PyObject> {
+	let keys = std::sync::Mutex::new(keys);
+	let next = PyCFunction::new_closure(
+		py,
+		None,
+		None,
+		move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<PyObject> {
+			let py = args.py();
+			let mut keys = keys.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+			keys.next(py)?.ok_or_else(|| PyStopIteration::new_err(()))
+		},
+	)?;
+	Ok(proxy_module(py)?.getattr("BorrowedKeys")?.call1((next,))?.into())
+}
+
+fn new_proxy(py: Python<'_>, lent: Arc<Lent>, protocol: Protocol) -> PyResult<PyObject> {
+	let module = proxy_module(py)?;
+	let type_name = lent.type_name;
+	let access = PyCFunction::new_closure(
+		py,
+		None,
+		None,
+		move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<PyObject> {
+			let (op, key, arg): (&str, &PyAny, &PyAny) = args.extract()?;
+			lent.access(args.py(), op, key, arg)
+		},
+	)?;
+	let class = match protocol {
+		Protocol::Object => "Borrowed",
+		Protocol::Sequence => "BorrowedSequence",
+		Protocol::Mapping => "BorrowedMapping",
+	};
+	Ok(module.getattr(class)?.call1((access, type_name))?.into())
+}
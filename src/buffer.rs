@@ -0,0 +1,312 @@
+//! Sharing Rust slices with Python through the buffer protocol, without copying.
+
+use pyo3::{
+	exceptions::PyBufferError, ffi, sync::GILOnceCell, types::PyType, AsPyPointer, Py, PyErr, PyObject, PyResult, Python, ToPyObject,
+};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+/// An element type of a slice that can be shared with Python as a buffer.
+///
+/// # Safety
+///
+/// Every bit pattern of the right size must be a valid value of the type,
+/// since Python code can write anything to a writable buffer,
+/// the type must not contain padding bytes, and [`FORMAT`](BufferElement::FORMAT) must be the `struct` module format
+/// of the type in native byte order.
+pub unsafe trait BufferElement: Copy {
+	/// The `struct` module format of this type, with a nul terminator.
+	const FORMAT: &'static [u8];
+}
+
+macro_rules! impl_buffer_element {
+	($($type:ty => $format:literal,)*) => {
+		$(unsafe impl BufferElement for $type {
+			const FORMAT: &'static [u8] = $format;
+		})*
+	};
+}
+
+impl_buffer_element! {
+	u8 => b"B\0",
+	i8 => b"b\0",
+	u16 => b"H\0",
+	i16 => b"h\0",
+	u32 => b"I\0",
+	i32 => b"i\0",
+	u64 => b"Q\0",
+	i64 => b"q\0",
+	usize => b"N\0",
+	isize => b"n\0",
+	f32 => b"f\0",
+	f64 => b"d\0",
+}
+
+/// A Rust slice that is shared with Python through the buffer protocol.
+///
+/// Python code can use it through `memoryview`, `array`, `struct`, numpy's
+/// `frombuffer`, or anything else that supports the buffer protocol, without
+/// converting every element to a Python object. A buffer of a `&mut [T]` is
+/// writable.
+///
+/// Within a `python!{}` block, use `'buffer var` for a read-only buffer of
+/// `var[..]`, or `'buffer mut var` for a writable one:
+///
+/// ```
+/// # use inline_python::python;
+/// let mut signal = vec![1.0f64, 2.0, 3.0];
+/// python! {
+///     with memoryview('buffer mut signal) as data:
+///         for i in range(len(data)):
+///             data[i] *= 2
+/// }
+/// assert_eq!(signal, [2.0, 4.0, 6.0]);
+/// ```
+///
+/// To share a slice with Python code in a [`Context`](crate::Context),
+/// use [`SliceBuffer::scope`] or [`SliceBuffer::scope_mut`].
+///
+/// The buffer refers to the memory of the slice directly, without copying it.
+/// When the buffer is dropped, which happens when the block or scope ends,
+/// Python code can no longer get a view of it.
+/// Views that were taken from it must be released before that, for example with
+/// `memoryview.release()`, a `with` statement, or by deleting the last reference to them.
+/// Since a view that is still alive would refer to the slice after it is no longer borrowed,
+/// dropping the buffer while it has views aborts the process.
+pub struct SliceBuffer<'a> {
+	exporter: PyObject,
+	borrow: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> SliceBuffer<'a> {
+	/// Share a slice with Python as a read-only buffer, for the duration of `f`.
+	///
+	/// ```
+	/// # use inline_python::{python, Context, SliceBuffer};
+	/// let c = Context::new();
+	/// let data = [1u8, 2, 3];
+	/// SliceBuffer::scope(&data, |buffer| {
+	///     c.set("data", buffer);
+	///     c.run(python! {
+	///         total = sum(memoryview(data))
+	///     });
+	/// });
+	/// assert_eq!(c.get::<u32>("total"), 6);
+	/// ```
+	pub fn scope<T: BufferElement, R>(data: &[T], f: impl FnOnce(&SliceBuffer) -> R) -> R {
+		f(&SliceBuffer::new(data))
+	}
+
+	/// Share a slice with Python as a writable buffer, for the duration of `f`.
+	pub fn scope_mut<T: BufferElement, R>(data: &mut [T], f: impl FnOnce(&SliceBuffer) -> R) -> R {
+		f(&SliceBuffer::new_mut(data))
+	}
+
+	/// Create a read-only buffer of a slice. Used by `'buffer var`.
+	///
+	/// The buffer must be dropped before the borrow ends, since Python code can use it until then.
+	/// This is not a public API: the `python!{}` macro moves it into the closures of the block,
+	/// which the crate drops at the end of the block.
+	/// Use [`SliceBuffer::scope`] instead.
+	///
+	/// This panics if the Python object can't be created.
+	#[doc(hidden)]
+	pub fn new<T: BufferElement>(data: &'a [T]) -> Self {
+		Self::with_exporter::<T>(data.as_ptr() as *mut c_void, std::mem::size_of_val(data), false)
+	}
+
+	/// Create a writable buffer of a slice. Used by `'buffer mut var`.
+	///
+	/// See [`SliceBuffer::new`]. Use [`SliceBuffer::scope_mut`] instead.
+	///
+	/// This panics if the Python object can't be created.
+	#[doc(hidden)]
+	pub fn new_mut<T: BufferElement>(data: &'a mut [T]) -> Self {
+		Self::with_exporter::<T>(data.as_mut_ptr() as *mut c_void, std::mem::size_of_val(data), true)
+	}
+
+	fn with_exporter<T: BufferElement>(buf: *mut c_void, len: usize, writable: bool) -> Self {
+		Python::with_gil(|py| {
+			let exporter = new_exporter(py, buf, len, std::mem::size_of::<T>(), T::FORMAT, writable).unwrap_or_else(|e| {
+				e.print(py);
+				panic!("Unable to create a Python buffer");
+			});
+			Self {
+				exporter,
+				borrow: PhantomData,
+			}
+		})
+	}
+}
+
+impl ToPyObject for SliceBuffer<'_> {
+	fn to_object(&self, py: Python) -> PyObject {
+		self.exporter.clone_ref(py)
+	}
+}
+
+impl Drop for SliceBuffer<'_> {
+	fn drop(&mut self) {
+		// The GIL keeps Python code from taking or releasing views in the meantime.
+		Python::with_gil(|py| unsafe {
+			let exporter = self.exporter.as_ptr() as *mut Exporter;
+			(*exporter).valid = false;
+			if (*exporter).exports != 0 {
+				PyBufferError::new_err(format!(
+					"{} view(s) of a Rust slice are still alive after the slice is no longer borrowed",
+					(*exporter).exports
+				))
+				.print(py);
+				std::process::abort();
+			}
+		});
+	}
+}
+
+/// The Python object that exports the buffer.
+///
+/// It points to the memory of the slice, and counts the views that are taken from it.
+///
+/// A zeroed object is a valid, invalidated buffer.
+#[repr(C)]
+struct Exporter {
+	ob_base: ffi::PyObject,
+	buf: *mut c_void,
+	/// The length in elements.
+	shape: ffi::Py_ssize_t,
+	itemsize: ffi::Py_ssize_t,
+	/// The length in bytes, which is the shape of an untyped view.
+	len: ffi::Py_ssize_t,
+	/// The stride of an untyped view.
+	byte_stride: ffi::Py_ssize_t,
+	format: *const c_char,
+	/// The number of views that have not been released yet.
+	exports: ffi::Py_ssize_t,
+	writable: bool,
+	valid: bool,
+}
+
+static EXPORTER_TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+fn exporter_type(py: Python<'_>) -> PyResult<&PyType> {
+	EXPORTER_TYPE
+		.get_or_try_init(py, || {
+			let mut slots = [
+				ffi::PyType_Slot {
+					slot: ffi::Py_bf_getbuffer,
+					pfunc: get_buffer as *mut c_void,
+				},
+				ffi::PyType_Slot {
+					slot: ffi::Py_bf_releasebuffer,
+					pfunc: release_buffer as *mut c_void,
+				},
+				ffi::PyType_Slot {
+					slot: ffi::Py_tp_dealloc,
+					pfunc: dealloc as *mut c_void,
+				},
+				ffi::PyType_Slot {
+					slot: 0,
+					pfunc: std::ptr::null_mut(),
+				},
+			];
+			let mut spec = ffi::PyType_Spec {
+				name: b"inline_python.SliceBuffer\0".as_ptr() as *const c_char,
+				basicsize: std::mem::size_of::<Exporter>() as c_int,
+				itemsize: 0,
+				flags: ffi::Py_TPFLAGS_DEFAULT as c_uint,
+				slots: slots.as_mut_ptr(),
+			};
+			let ty: &PyType = unsafe { py.from_owned_ptr_or_err(ffi::PyType_FromSpec(&mut spec))? };
+			Ok(ty.into())
+		})
+		.map(|ty| ty.as_ref(py))
+}
+
+fn new_exporter(
+	py: Python<'_>,
+	buf: *mut c_void,
+	len: usize,
+	itemsize: usize,
+	format: &'static [u8],
+	writable: bool,
+) -> PyResult<PyObject> {
+	let ty = exporter_type(py)?.as_type_ptr();
+	unsafe {
+		let alloc: ffi::allocfunc = std::mem::transmute(ffi::PyType_GetSlot(ty, ffi::Py_tp_alloc));
+		let object = alloc(ty, 0);
+		if object.is_null() {
+			return Err(PyErr::fetch(py));
+		}
+		let exporter = object as *mut Exporter;
+		(*exporter).buf = buf;
+		(*exporter).shape = (len / itemsize) as ffi::Py_ssize_t;
+		(*exporter).itemsize = itemsize as ffi::Py_ssize_t;
+		(*exporter).len = len as ffi::Py_ssize_t;
+		(*exporter).byte_stride = 1;
+		(*exporter).format = format.as_ptr() as *const c_char;
+		(*exporter).writable = writable;
+		(*exporter).valid = true;
+		Ok(PyObject::from_owned_ptr(py, object))
+	}
+}
+
+unsafe extern "C" fn get_buffer(object: *mut ffi::PyObject, view: *mut ffi::Py_buffer, flags: c_int) -> c_int {
+	let py = Python::assume_gil_acquired();
+	let exporter = object as *mut Exporter;
+	if !(*exporter).valid {
+		PyBufferError::new_err("the Rust slice of this buffer is no longer borrowed").restore(py);
+		return -1;
+	}
+	if flags & ffi::PyBUF_WRITABLE != 0 && !(*exporter).writable {
+		PyBufferError::new_err("buffer is read-only").restore(py);
+		return -1;
+	}
+	// Without a format, the buffer is viewed as unsigned bytes.
+	let typed = flags & ffi::PyBUF_FORMAT != 0;
+	(*exporter).exports += 1;
+	ffi::Py_INCREF(object);
+	(*view).obj = object;
+	(*view).buf = (*exporter).buf;
+	(*view).len = (*exporter).len;
+	(*view).readonly = !(*exporter).writable as c_int;
+	(*view).ndim = 1;
+	if typed {
+		(*view).itemsize = (*exporter).itemsize;
+		(*view).format = (*exporter).format as *mut c_char;
+	} else {
+		(*view).itemsize = 1;
+		(*view).format = std::ptr::null_mut();
+	}
+	(*view).shape = if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+		if typed {
+			&mut (*exporter).shape
+		} else {
+			&mut (*exporter).len
+		}
+	} else {
+		std::ptr::null_mut()
+	};
+	(*view).strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES {
+		if typed {
+			&mut (*exporter).itemsize
+		} else {
+			&mut (*exporter).byte_stride
+		}
+	} else {
+		std::ptr::null_mut()
+	};
+	(*view).suboffsets = std::ptr::null_mut();
+	(*view).internal = std::ptr::null_mut();
+	0
+}
+
+unsafe extern "C" fn release_buffer(object: *mut ffi::PyObject, _view: *mut ffi::Py_buffer) {
+	(*(object as *mut Exporter)).exports -= 1;
+}
+
+unsafe extern "C" fn dealloc(object: *mut ffi::PyObject) {
+	let ty = ffi::Py_TYPE(object);
+	let free: ffi::freefunc = std::mem::transmute(ffi::PyType_GetSlot(ty, ffi::Py_tp_free));
+	free(object as *mut c_void);
+	ffi::Py_DECREF(ty as *mut ffi::PyObject);
+}
@@ -0,0 +1,183 @@
+//! [`Borrowable`] implementations for Rust collections, as Python sequences and mappings.
+
+use crate::borrow::{no_attribute, Borrowable, Protocol};
+use pyo3::{
+	exceptions::{PyIndexError, PyKeyError, PyTypeError},
+	types::{PyAny, PyList, PySlice},
+	FromPyObject, PyObject, PyResult, Python, ToPyObject,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::os::raw::c_long;
+
+/// Convert a Python index, which can be negative, to an index into a sequence of length `len`.
+fn sequence_index(len: usize, index: &PyAny) -> PyResult<usize> {
+	let index: isize = index
+		.extract()
+		.map_err(|_| PyTypeError::new_err(format!("indices must be integers, not {}", index.get_type().name().unwrap_or("?"))))?;
+	let index = if index < 0 { index + len as isize } else { index };
+	if index < 0 || index >= len as isize {
+		return Err(PyIndexError::new_err("index out of range"));
+	}
+	Ok(index as usize)
+}
+
+/// Extract an item, reporting a failure as a `TypeError` that names the Rust type.
+fn extract_item<T: for<'p> FromPyObject<'p>>(item: &PyAny) -> PyResult<T> {
+	item.extract().map_err(|e| {
+		let py = item.py();
+		let error = PyTypeError::new_err(format!(
+			"unable to convert item to `{}`: {}",
+			std::any::type_name::<T>(),
+			e.value(py)
+		));
+		error.set_cause(py, Some(e));
+		error
+	})
+}
+
+/// A `Vec<T>` is a Python sequence, which supports slicing for reading, and can grow and shrink.
+///
+/// ```
+/// # use inline_python::python;
+/// let mut values = vec![3, 1, 2];
+/// python! {
+///     v = 'borrow mut values
+///     v.append(max(v) + 1)
+///     del v[0]
+///     assert v[-1] == 4 and v[:2] == [1, 2]
+/// }
+/// assert_eq!(values, [1, 2, 4]);
+/// ```
+impl<T: ToPyObject + for<'p> FromPyObject<'p>> Borrowable for Vec<T> {
+	fn get_attr(&self, _py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		Err(no_attribute::<Self>(name))
+	}
+
+	fn protocol(&self) -> Protocol {
+		Protocol::Sequence
+	}
+
+	fn length(&self) -> PyResult<usize> {
+		Ok(Vec::len(self))
+	}
+
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		if let Ok(slice) = key.downcast::<PySlice>() {
+			let indices = slice.indices(Vec::len(self) as c_long)?;
+			let items = (0..indices.slicelength).map(|i| self[(indices.start + i * indices.step) as usize].to_object(py));
+			return Ok(PyList::new(py, items).into());
+		}
+		Ok(self[sequence_index(Vec::len(self), key)?].to_object(py))
+	}
+
+	fn set_item(&mut self, _py: Python<'_>, key: &PyAny, item: &PyAny) -> PyResult<()> {
+		let index = sequence_index(Vec::len(self), key)?;
+		self[index] = extract_item(item)?;
+		Ok(())
+	}
+
+	fn del_item(&mut self, _py: Python<'_>, key: &PyAny) -> PyResult<()> {
+		let index = sequence_index(Vec::len(self), key)?;
+		self.remove(index);
+		Ok(())
+	}
+
+	fn insert_item(&mut self, _py: Python<'_>, index: usize, item: &PyAny) -> PyResult<()> {
+		self.insert(index, extract_item(item)?);
+		Ok(())
+	}
+}
+
+/// A `HashMap<K, V>` is a Python mapping.
+///
+/// A key that can't be converted to `K` is not in the map.
+impl<K, V, S> Borrowable for HashMap<K, V, S>
+where
+	K: ToPyObject + for<'p> FromPyObject<'p> + Eq + Hash,
+	V: ToPyObject + for<'p> FromPyObject<'p>,
+	S: BuildHasher,
+{
+	fn get_attr(&self, _py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		Err(no_attribute::<Self>(name))
+	}
+
+	fn protocol(&self) -> Protocol {
+		Protocol::Mapping
+	}
+
+	fn length(&self) -> PyResult<usize> {
+		Ok(HashMap::len(self))
+	}
+
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		key.extract::<K>()
+			.ok()
+			.and_then(|k| self.get(&k))
+			.map(|v| v.to_object(py))
+			.ok_or_else(|| PyKeyError::new_err(key.to_object(py)))
+	}
+
+	fn set_item(&mut self, _py: Python<'_>, key: &PyAny, item: &PyAny) -> PyResult<()> {
+		self.insert(extract_item(key)?, extract_item(item)?);
+		Ok(())
+	}
+
+	fn del_item(&mut self, py: Python<'_>, key: &PyAny) -> PyResult<()> {
+		key.extract::<K>()
+			.ok()
+			.and_then(|k| self.remove(&k))
+			.map(drop)
+			.ok_or_else(|| PyKeyError::new_err(key.to_object(py)))
+	}
+
+	fn keys(&self) -> PyResult<Box<dyn Iterator<Item = &dyn ToPyObject> + '_>> {
+		Ok(Box::new(HashMap::keys(self).map(|k| k as &dyn ToPyObject)))
+	}
+}
+
+/// A `BTreeMap<K, V>` is a Python mapping, which iterates over its keys in order.
+///
+/// A key that can't be converted to `K` is not in the map.
+impl<K, V> Borrowable for BTreeMap<K, V>
+where
+	K: ToPyObject + for<'p> FromPyObject<'p> + Ord,
+	V: ToPyObject + for<'p> FromPyObject<'p>,
+{
+	fn get_attr(&self, _py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		Err(no_attribute::<Self>(name))
+	}
+
+	fn protocol(&self) -> Protocol {
+		Protocol::Mapping
+	}
+
+	fn length(&self) -> PyResult<usize> {
+		Ok(BTreeMap::len(self))
+	}
+
+	fn get_item(&self, py: Python<'_>, key: &PyAny) -> PyResult<PyObject> {
+		key.extract::<K>()
+			.ok()
+			.and_then(|k| self.get(&k))
+			.map(|v| v.to_object(py))
+			.ok_or_else(|| PyKeyError::new_err(key.to_object(py)))
+	}
+
+	fn set_item(&mut self, _py: Python<'_>, key: &PyAny, item: &PyAny) -> PyResult<()> {
+		self.insert(extract_item(key)?, extract_item(item)?);
+		Ok(())
+	}
+
+	fn del_item(&mut self, py: Python<'_>, key: &PyAny) -> PyResult<()> {
+		key.extract::<K>()
+			.ok()
+			.and_then(|k| self.remove(&k))
+			.map(drop)
+			.ok_or_else(|| PyKeyError::new_err(key.to_object(py)))
+	}
+
+	fn keys(&self) -> PyResult<Box<dyn Iterator<Item = &dyn ToPyObject> + '_>> {
+		Ok(Box::new(BTreeMap::keys(self).map(|k| k as &dyn ToPyObject)))
+	}
+}
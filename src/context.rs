@@ -1,9 +1,18 @@
-use crate::run::run_python_code;
-use crate::PythonBlock;
+use crate::module::register_module;
+use crate::output::{new_reader, new_writer};
+use crate::run::{compile_file, compile_source, run_code_object, run_python_code, start_python_code};
+use crate::warning::new_hook;
+use crate::{
+	AsyncPythonBlock, CapturedOutput, FromContext, FunctionArgs, InlinePythonError, IntoContext, IntoPyFunction, PyWarning, PythonBlock,
+	PythonFunction, SetVariables,
+};
 use pyo3::{
-	types::{PyCFunction, PyDict},
-	FromPyObject, Py, PyResult, Python, ToPyObject,
+	exceptions::PyNameError,
+	types::{PyCFunction, PyDict, PyList, PyModule},
+	FromPyObject, Py, PyAny, PyObject, PyResult, Python, ToPyObject,
 };
+use std::io::{Read, Write};
+use std::path::Path;
 
 /// An execution context for Python code.
 ///
@@ -39,6 +48,13 @@ use pyo3::{
 /// ```
 pub struct Context {
 	pub(crate) globals: Py<PyDict>,
+	/// The `sys` streams to use while running code in this context, by name.
+	pub(crate) streams: Py<PyDict>,
+	/// The warning settings to apply while running code in this context.
+	pub(crate) warnings: Py<PyDict>,
+	/// Whether to forward `logging` records while running code in this context.
+	#[cfg(feature = "log")]
+	pub(crate) forward_logging: std::sync::atomic::AtomicBool,
 }
 
 impl Context {
@@ -59,7 +75,7 @@ impl Context {
 	///
 	/// This function panics if it fails to create the context.
 	pub fn new_with_gil(py: Python) -> Self {
-		match Self::try_new(py) {
+		match Self::try_new_with_gil(py) {
 			Ok(x) => x,
 			Err(error) => {
 				error.print(py);
@@ -68,9 +84,28 @@ impl Context {
 		}
 	}
 
-	fn try_new(py: Python) -> PyResult<Self> {
+	/// Create a new context for running Python code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_new_with_gil`] instead.
+	pub fn try_new() -> Result<Self, InlinePythonError> {
+		Python::with_gil(Self::try_new_with_gil)
+	}
+
+	/// Create a new context for running Python code.
+	///
+	/// You must acquire the GIL to call this function.
+	pub fn try_new_with_gil(py: Python) -> Result<Self, InlinePythonError> {
+		let globals = py
+			.import("__main__")
+			.and_then(|main| main.dict().copy())
+			.map_err(|e| InlinePythonError::new(py, e))?;
 		Ok(Self {
-			globals: py.import("__main__")?.dict().copy()?.into(),
+			globals: globals.into(),
+			streams: PyDict::new(py).into(),
+			warnings: PyDict::new(py).into(),
+			#[cfg(feature = "log")]
+			forward_logging: std::sync::atomic::AtomicBool::new(false),
 		})
 	}
 
@@ -105,6 +140,104 @@ impl Context {
 		}
 	}
 
+	/// Retrieve a global variable from the context.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_get_with_gil`] instead.
+	///
+	/// A missing variable is reported as a Python `NameError`.
+	pub fn try_get<T: for<'p> FromPyObject<'p>>(&self, name: &str) -> Result<T, InlinePythonError> {
+		Python::with_gil(|py| self.try_get_with_gil(py, name))
+	}
+
+	/// Retrieve a global variable from the context.
+	///
+	/// A missing variable is reported as a Python `NameError`.
+	pub fn try_get_with_gil<'p, T: FromPyObject<'p>>(&'p self, py: Python<'p>, name: &str) -> Result<T, InlinePythonError> {
+		let value = self
+			.globals(py)
+			.get_item(name)
+			.ok_or_else(|| PyNameError::new_err(format!("name '{}' is not defined", name)));
+		value.and_then(FromPyObject::extract).map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Extract a value from several global variables at once.
+	///
+	/// This is typically used with a struct that derives [`FromContext`],
+	/// where each field is taken from the global variable with the same name.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::extract_with_gil`] instead.
+	///
+	/// This function panics if any of the variables doesn't exist, or a conversion fails.
+	pub fn extract<T: FromContext>(&self) -> T {
+		Python::with_gil(|py| self.extract_with_gil(py))
+	}
+
+	/// Extract a value from several global variables at once.
+	///
+	/// See [`Context::extract`].
+	///
+	/// This function panics if any of the variables doesn't exist, or a conversion fails.
+	pub fn extract_with_gil<T: FromContext>(&self, py: Python<'_>) -> T {
+		match self.try_extract_with_gil(py) {
+			Ok(value) => value,
+			Err(e) => {
+				e.print(py);
+				panic!("Unable to extract `{}` from the context", std::any::type_name::<T>());
+			}
+		}
+	}
+
+	/// Extract a value from several global variables at once.
+	///
+	/// All missing and mistyped variables are reported together in one error.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_extract_with_gil`] instead.
+	pub fn try_extract<T: FromContext>(&self) -> Result<T, InlinePythonError> {
+		Python::with_gil(|py| self.try_extract_with_gil(py))
+	}
+
+	/// Extract a value from several global variables at once.
+	///
+	/// See [`Context::try_extract`].
+	pub fn try_extract_with_gil<T: FromContext>(&self, py: Python<'_>) -> Result<T, InlinePythonError> {
+		T::from_context(py, self)
+	}
+
+	/// Get a handle to a Python function defined in the context.
+	///
+	/// The handle has typed arguments and return value, and can be called any number of times.
+	/// See [`PythonFunction`].
+	///
+	/// A missing function is reported as a Python `NameError`, and a function
+	/// that does not take `Args` as positional arguments as a `TypeError`.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::function_with_gil`] instead.
+	pub fn function<Args: FunctionArgs, R: for<'p> FromPyObject<'p>>(
+		&self,
+		name: &str,
+	) -> Result<PythonFunction<Args, R>, InlinePythonError> {
+		Python::with_gil(|py| self.function_with_gil(py, name))
+	}
+
+	/// Get a handle to a Python function defined in the context.
+	///
+	/// See [`Context::function`].
+	pub fn function_with_gil<Args: FunctionArgs, R: for<'p> FromPyObject<'p>>(
+		&self,
+		py: Python<'_>,
+		name: &str,
+	) -> Result<PythonFunction<Args, R>, InlinePythonError> {
+		self.globals(py)
+			.get_item(name)
+			.ok_or_else(|| PyNameError::new_err(format!("name '{}' is not defined", name)))
+			.and_then(|function| PythonFunction::new(py, name, function))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
 	/// Set a global variable in the context.
 	///
 	/// This function temporarily acquires the GIL.
@@ -119,7 +252,133 @@ impl Context {
 	///
 	/// This function panics if the conversion fails.
 	pub fn set_with_gil<'p, T: ToPyObject>(&self, py: Python<'p>, name: &str, value: T) {
-		match self.globals(py).set_item(name, value) {
+		match self.try_set_with_gil(py, name, value) {
+			Ok(()) => (),
+			Err(e) => {
+				e.print(py);
+				panic!("Unable to set `{}` from a `{}`", name, std::any::type_name::<T>());
+			}
+		}
+	}
+
+	/// Set a global variable in the context.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_with_gil`] instead.
+	pub fn try_set<T: ToPyObject>(&self, name: &str, value: T) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_with_gil(py, name, value))
+	}
+
+	/// Set a global variable in the context.
+	pub fn try_set_with_gil<'p, T: ToPyObject>(&self, py: Python<'p>, name: &str, value: T) -> Result<(), InlinePythonError> {
+		self.globals(py).set_item(name, value).map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Set several global variables at once.
+	///
+	/// This is typically used with a struct that derives [`IntoContext`],
+	/// where each field is stored in the global variable with the same name.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::set_all_with_gil`] instead.
+	///
+	/// This function panics if a conversion fails.
+	pub fn set_all<T: IntoContext>(&self, value: T) {
+		Python::with_gil(|py| self.set_all_with_gil(py, value));
+	}
+
+	/// Set several global variables at once.
+	///
+	/// See [`Context::set_all`].
+	///
+	/// This function panics if a conversion fails.
+	pub fn set_all_with_gil<T: IntoContext>(&self, py: Python<'_>, value: T) {
+		if let Err(e) = self.try_set_all_with_gil(py, value) {
+			e.print(py);
+			panic!("Unable to set the variables of a `{}`", std::any::type_name::<T>());
+		}
+	}
+
+	/// Set several global variables at once.
+	///
+	/// See [`Context::set_all`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_all_with_gil`] instead.
+	pub fn try_set_all<T: IntoContext>(&self, value: T) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_all_with_gil(py, value))
+	}
+
+	/// Set several global variables at once.
+	///
+	/// See [`Context::set_all`].
+	pub fn try_set_all_with_gil<T: IntoContext>(&self, py: Python<'_>, value: T) -> Result<(), InlinePythonError> {
+		value.into_context(py, self)
+	}
+
+	/// Retrieve a global variable from the context, using its `serde::Deserialize` implementation.
+	///
+	/// This is available with the `serde` feature.
+	/// See [`Serde`](crate::Serde) for how Python objects are converted.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::get_serde_with_gil`] instead.
+	///
+	/// This function panics if the variable doesn't exist, or the conversion fails.
+	#[cfg(feature = "serde")]
+	pub fn get_serde<T: serde::de::DeserializeOwned>(&self, name: &str) -> T {
+		Python::with_gil(|py| self.get_serde_with_gil(py, name))
+	}
+
+	/// Retrieve a global variable from the context, using its `serde::Deserialize` implementation.
+	///
+	/// This function panics if the variable doesn't exist, or the conversion fails.
+	#[cfg(feature = "serde")]
+	pub fn get_serde_with_gil<T: serde::de::DeserializeOwned>(&self, py: Python<'_>, name: &str) -> T {
+		let crate::Serde(value) = self.get_with_gil(py, name);
+		value
+	}
+
+	/// Retrieve a global variable from the context, using its `serde::Deserialize` implementation.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_get_serde_with_gil`] instead.
+	///
+	/// A missing variable is reported as a Python `NameError`,
+	/// and a failed conversion as a `ValueError` or `TypeError`.
+	#[cfg(feature = "serde")]
+	pub fn try_get_serde<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<T, InlinePythonError> {
+		Python::with_gil(|py| self.try_get_serde_with_gil(py, name))
+	}
+
+	/// Retrieve a global variable from the context, using its `serde::Deserialize` implementation.
+	///
+	/// See [`Context::try_get_serde`].
+	#[cfg(feature = "serde")]
+	pub fn try_get_serde_with_gil<T: serde::de::DeserializeOwned>(&self, py: Python<'_>, name: &str) -> Result<T, InlinePythonError> {
+		self.try_get_with_gil(py, name).map(|crate::Serde(value)| value)
+	}
+
+	/// Set a global variable in the context, using its `serde::Serialize` implementation.
+	///
+	/// This is available with the `serde` feature.
+	/// See [`Serde`](crate::Serde) for how values are converted.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::set_serde_with_gil`] instead.
+	///
+	/// This function panics if the conversion fails.
+	#[cfg(feature = "serde")]
+	pub fn set_serde<T: serde::Serialize + ?Sized>(&self, name: &str, value: &T) {
+		Python::with_gil(|py| self.set_serde_with_gil(py, name, value));
+	}
+
+	/// Set a global variable in the context, using its `serde::Serialize` implementation.
+	///
+	/// This function panics if the conversion fails.
+	#[cfg(feature = "serde")]
+	pub fn set_serde_with_gil<T: serde::Serialize + ?Sized>(&self, py: Python<'_>, name: &str, value: &T) {
+		match self.try_set_serde_with_gil(py, name, value) {
 			Ok(()) => (),
 			Err(e) => {
 				e.print(py);
@@ -128,9 +387,32 @@ impl Context {
 		}
 	}
 
-	/// Add a wrapped `#[pyfunction]` or `#[pymodule]` using its own `__name__`.
+	/// Set a global variable in the context, using its `serde::Serialize` implementation.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_serde_with_gil`] instead.
+	#[cfg(feature = "serde")]
+	pub fn try_set_serde<T: serde::Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_serde_with_gil(py, name, value))
+	}
+
+	/// Set a global variable in the context, using its `serde::Serialize` implementation.
+	#[cfg(feature = "serde")]
+	pub fn try_set_serde_with_gil<T: serde::Serialize + ?Sized>(
+		&self,
+		py: Python<'_>,
+		name: &str,
+		value: &T,
+	) -> Result<(), InlinePythonError> {
+		crate::to_python(py, value)
+			.and_then(|value| self.globals(py).set_item(name, value))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Add a wrapped `#[pyfunction]` using its own `__name__`.
 	///
-	/// Use this with `pyo3::wrap_pyfunction` or `pyo3::wrap_pymodule`.
+	/// Use this with `pyo3::wrap_pyfunction`.
+	/// For a `#[pymodule]`, use [`Context::add_module`] instead.
 	///
 	/// ```ignore
 	/// # use inline_python::{Context, python};
@@ -158,7 +440,7 @@ impl Context {
 		Python::with_gil(|py| self.add_wrapped_with_gil(py, wrapper));
 	}
 
-	/// Add a wrapped `#[pyfunction]` or `#[pymodule]` using its own `__name__`.
+	/// Add a wrapped `#[pyfunction]` using its own `__name__`.
 	///
 	/// See [Context::add_wrapped].
 	pub fn add_wrapped_with_gil<'p>(&self, py: Python<'p>, wrapper: &impl Fn(Python) -> PyResult<&PyCFunction>) {
@@ -167,41 +449,1040 @@ impl Context {
 		self.set_with_gil(py, name.extract().unwrap(), obj)
 	}
 
-	/// Run Python code using this context.
+	/// Add a wrapped `#[pyfunction]` using its own `__name__`.
 	///
-	/// This function should be called using the `python!{}` macro:
+	/// See [Context::add_wrapped].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_add_wrapped_with_gil`] instead.
+	pub fn try_add_wrapped(&self, wrapper: &impl Fn(Python) -> PyResult<&PyCFunction>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_add_wrapped_with_gil(py, wrapper))
+	}
+
+	/// Add a wrapped `#[pyfunction]` using its own `__name__`.
+	///
+	/// See [Context::add_wrapped].
+	pub fn try_add_wrapped_with_gil<'p>(
+		&self,
+		py: Python<'p>,
+		wrapper: &impl Fn(Python) -> PyResult<&PyCFunction>,
+	) -> Result<(), InlinePythonError> {
+		let result = (|| -> PyResult<()> {
+			let obj = wrapper(py)?;
+			let name: &str = obj.getattr("__name__")?.extract()?;
+			self.globals(py).set_item(name, obj)
+		})();
+		result.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Add a wrapped `#[pymodule]` using its own `__name__`.
+	///
+	/// Use this with `pyo3::wrap_pymodule`.
+	///
+	/// ```ignore
+	/// # use inline_python::{Context, python};
+	/// use pyo3::{prelude::*, wrap_pymodule};
+	///
+	/// #[pymodule]
+	/// fn fast(_py: Python, m: &PyModule) -> PyResult<()> {
+	///     m.add("five", 5)
+	/// }
+	///
+	/// fn main() {
+	///     let c = Context::new();
+	///
+	///     c.add_module(wrap_pymodule!(fast));
+	///
+	///     c.run(python! {
+	///         assert fast.five == 5
+	///     });
+	/// }
+	/// ```
+	///
+	/// To make the module importable, use [`Context::add_module_as`] instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::add_module_with_gil`] instead.
+	///
+	/// This function panics if it fails to add the module.
+	pub fn add_module(&self, wrapper: &impl Fn(Python) -> Py<PyModule>) {
+		Python::with_gil(|py| self.add_module_with_gil(py, wrapper));
+	}
+
+	/// Add a wrapped `#[pymodule]` using its own `__name__`.
+	///
+	/// See [`Context::add_module`].
+	///
+	/// This function panics if it fails to add the module.
+	pub fn add_module_with_gil(&self, py: Python<'_>, wrapper: &impl Fn(Python) -> Py<PyModule>) {
+		if let Err(e) = self.try_add_module_with_gil(py, wrapper) {
+			e.print(py);
+			panic!("Unable to add module");
+		}
+	}
+
+	/// Add a wrapped `#[pymodule]` using its own `__name__`.
+	///
+	/// See [`Context::add_module`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_add_module_with_gil`] instead.
+	pub fn try_add_module(&self, wrapper: &impl Fn(Python) -> Py<PyModule>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_add_module_with_gil(py, wrapper))
+	}
+
+	/// Add a wrapped `#[pymodule]` using its own `__name__`.
+	///
+	/// See [`Context::add_module`].
+	pub fn try_add_module_with_gil(&self, py: Python<'_>, wrapper: &impl Fn(Python) -> Py<PyModule>) -> Result<(), InlinePythonError> {
+		let module = wrapper(py).into_ref(py);
+		module
+			.name()
+			.and_then(|name| self.globals(py).set_item(name, module))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Make a wrapped `#[pymodule]` importable under the given dotted name.
+	///
+	/// The module is registered in `sys.modules`, such that `import` statements
+	/// can find it. Missing parent packages are created, and submodules added
+	/// with `PyModule::add_submodule` are registered as well.
+	/// Note that `sys.modules` is shared by all contexts.
+	///
+	/// ```ignore
+	/// # use inline_python::{Context, python};
+	/// use pyo3::{prelude::*, wrap_pymodule};
+	///
+	/// #[pymodule]
+	/// fn fast(_py: Python, m: &PyModule) -> PyResult<()> {
+	///     m.add("five", 5)
+	/// }
+	///
+	/// fn main() {
+	///     let c = Context::new();
+	///
+	///     c.add_module_as("mycrate.fast", wrap_pymodule!(fast));
+	///
+	///     c.run(python! {
+	///         from mycrate.fast import five
+	///         assert five == 5
+	///     });
+	/// }
+	/// ```
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::add_module_as_with_gil`] instead.
+	///
+	/// This function panics if it fails to register the module.
+	pub fn add_module_as(&self, name: &str, wrapper: &impl Fn(Python) -> Py<PyModule>) {
+		Python::with_gil(|py| self.add_module_as_with_gil(py, name, wrapper));
+	}
+
+	/// Make a wrapped `#[pymodule]` importable under the given dotted name.
+	///
+	/// See [`Context::add_module_as`].
+	///
+	/// This function panics if it fails to register the module.
+	pub fn add_module_as_with_gil(&self, py: Python<'_>, name: &str, wrapper: &impl Fn(Python) -> Py<PyModule>) {
+		if let Err(e) = self.try_add_module_as_with_gil(py, name, wrapper) {
+			e.print(py);
+			panic!("Unable to register module `{}`", name);
+		}
+	}
+
+	/// Make a wrapped `#[pymodule]` importable under the given dotted name.
+	///
+	/// See [`Context::add_module_as`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_add_module_as_with_gil`] instead.
+	pub fn try_add_module_as(&self, name: &str, wrapper: &impl Fn(Python) -> Py<PyModule>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_add_module_as_with_gil(py, name, wrapper))
+	}
+
+	/// Make a wrapped `#[pymodule]` importable under the given dotted name.
+	///
+	/// See [`Context::add_module_as`].
+	pub fn try_add_module_as_with_gil(
+		&self,
+		py: Python<'_>,
+		name: &str,
+		wrapper: &impl Fn(Python) -> Py<PyModule>,
+	) -> Result<(), InlinePythonError> {
+		register_module(py, name, wrapper(py).into_ref(py)).map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Add a Rust closure as a Python function.
+	///
+	/// Unlike a `#[pyfunction]`, the closure can capture state.
+	/// Its arguments are converted from Python using [`FromPyObject`], and its
+	/// result is converted back to Python. A closure returning a `Result` raises
+	/// its error as a Python exception.
 	///
 	/// ```
 	/// # use inline_python::{Context, python};
+	/// use std::sync::atomic::{AtomicUsize, Ordering};
+	/// use std::sync::Arc;
+	///
+	/// let calls = Arc::new(AtomicUsize::new(0));
 	/// let c = Context::new();
 	///
-	/// c.run(python!{
-	///     print("Hello World")
+	/// let counter = calls.clone();
+	/// c.add_fn("add", move |a: i32, b: i32| {
+	///     counter.fetch_add(1, Ordering::Relaxed);
+	///     a + b
+	/// });
+	///
+	/// c.run(python! {
+	///     assert add(1, 2) == 3
 	/// });
+	///
+	/// assert_eq!(calls.load(Ordering::Relaxed), 1);
 	/// ```
 	///
 	/// This function temporarily acquires the GIL.
-	/// If you already have the GIL, you can use [`Context::run_with_gil`] instead.
+	/// If you already have the GIL, you can use [`Context::add_fn_with_gil`] instead.
 	///
-	/// This function panics if the Python code fails.
-	pub fn run<F: FnOnce(&PyDict)>(&self, code: PythonBlock<F>) {
-		Python::with_gil(|py| self.run_with_gil(py, code));
+	/// This function panics if it fails to create the function.
+	pub fn add_fn<Args>(&self, name: &str, f: impl IntoPyFunction<Args>) {
+		Python::with_gil(|py| self.add_fn_with_gil(py, name, f));
 	}
 
-	/// Run Python code using this context.
+	/// Add a Rust closure as a Python function.
 	///
-	/// This function should be called using the `python!{}` macro, just like
-	/// [`Context::run`].
+	/// See [`Context::add_fn`].
 	///
-	/// This function panics if the Python code fails.
-	pub fn run_with_gil<F: FnOnce(&PyDict)>(&self, py: Python<'_>, code: PythonBlock<F>) {
-		(code.set_variables)(self.globals(py));
-		match run_python_code(py, self, code.bytecode) {
-			Ok(_) => (),
+	/// This function panics if it fails to create the function.
+	pub fn add_fn_with_gil<Args>(&self, py: Python<'_>, name: &str, f: impl IntoPyFunction<Args>) {
+		match self.try_add_fn_with_gil(py, name, f) {
+			Ok(()) => (),
 			Err(e) => {
 				e.print(py);
-				panic!("{}", "python!{...} failed to execute");
+				panic!("Unable to add function `{}`", name);
 			}
 		}
 	}
+
+	/// Add a Rust closure as a Python function.
+	///
+	/// See [`Context::add_fn`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_add_fn_with_gil`] instead.
+	pub fn try_add_fn<Args>(&self, name: &str, f: impl IntoPyFunction<Args>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_add_fn_with_gil(py, name, f))
+	}
+
+	/// Add a Rust closure as a Python function.
+	///
+	/// See [`Context::add_fn`].
+	pub fn try_add_fn_with_gil<Args>(&self, py: Python<'_>, name: &str, f: impl IntoPyFunction<Args>) -> Result<(), InlinePythonError> {
+		f.into_py_function(py)
+			.and_then(|function| self.globals(py).set_item(name, function))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Route `sys.stdout` to a Rust writer while this context runs Python code.
+	///
+	/// `sys.stdout` routes to this writer while running code with [`Context::run`]
+	/// and the other `run` and `eval` methods, and the writer is flushed afterwards.
+	/// To route the output of all Python code, use [`set_stdout`](crate::set_stdout) instead.
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// # use std::sync::{Arc, Mutex};
+	/// #[derive(Clone, Default)]
+	/// struct Buffer(Arc<Mutex<Vec<u8>>>);
+	///
+	/// impl std::io::Write for Buffer {
+	///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+	///         self.0.lock().unwrap().write(buf)
+	///     }
+	///     fn flush(&mut self) -> std::io::Result<()> {
+	///         Ok(())
+	///     }
+	/// }
+	///
+	/// let output = Buffer::default();
+	/// let c = Context::new();
+	/// c.set_stdout(output.clone());
+	/// c.run(python! {
+	///     print("hello")
+	/// });
+	/// assert_eq!(*output.0.lock().unwrap(), b"hello\n");
+	/// ```
+	///
+	/// This only affects the thread (or `asyncio` task) that runs the code.
+	/// Output of Python code in other threads, including threads started by
+	/// the code itself, doesn't go to this writer.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::set_stdout_with_gil`] instead.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stdout(&self, writer: impl Write + Send + 'static) {
+		Python::with_gil(|py| self.set_stdout_with_gil(py, writer));
+	}
+
+	/// Route `sys.stdout` to a Rust writer while this context runs Python code.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stdout_with_gil(&self, py: Python<'_>, writer: impl Write + Send + 'static) {
+		if let Err(e) = self.try_set_stdout_with_gil(py, writer) {
+			e.print(py);
+			panic!("Unable to set sys.stdout for the context");
+		}
+	}
+
+	/// Route `sys.stdout` to a Rust writer while this context runs Python code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_stdout_with_gil`] instead.
+	pub fn try_set_stdout(&self, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_stdout_with_gil(py, writer))
+	}
+
+	/// Route `sys.stdout` to a Rust writer while this context runs Python code.
+	pub fn try_set_stdout_with_gil(&self, py: Python<'_>, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+		self.set_stream(py, "stdout", new_writer(py, writer))
+	}
+
+	/// Route `sys.stderr` to a Rust writer while this context runs Python code.
+	///
+	/// See [`Context::set_stdout`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::set_stderr_with_gil`] instead.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stderr(&self, writer: impl Write + Send + 'static) {
+		Python::with_gil(|py| self.set_stderr_with_gil(py, writer));
+	}
+
+	/// Route `sys.stderr` to a Rust writer while this context runs Python code.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stderr_with_gil(&self, py: Python<'_>, writer: impl Write + Send + 'static) {
+		if let Err(e) = self.try_set_stderr_with_gil(py, writer) {
+			e.print(py);
+			panic!("Unable to set sys.stderr for the context");
+		}
+	}
+
+	/// Route `sys.stderr` to a Rust writer while this context runs Python code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_stderr_with_gil`] instead.
+	pub fn try_set_stderr(&self, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_stderr_with_gil(py, writer))
+	}
+
+	/// Route `sys.stderr` to a Rust writer while this context runs Python code.
+	pub fn try_set_stderr_with_gil(&self, py: Python<'_>, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+		self.set_stream(py, "stderr", new_writer(py, writer))
+	}
+
+	/// Feed `sys.stdin` from a Rust reader while this context runs Python code.
+	///
+	/// The stream supports `input()`, `readline()`, iteration over lines and `read(n)`.
+	/// It is kept by the context, so data that was read ahead from `reader`
+	/// is still available to code that runs later in this context.
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	/// c.set_stdin(&b"Ferris\n3\n"[..]);
+	/// c.run(python! {
+	///     name = input("name: ")
+	///     count = int(input())
+	/// });
+	/// assert_eq!(c.get::<String>("name"), "Ferris");
+	/// assert_eq!(c.get::<i32>("count"), 3);
+	/// ```
+	///
+	/// To feed all Python code, use [`set_stdin`](crate::set_stdin) instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::set_stdin_with_gil`] instead.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stdin(&self, reader: impl Read + Send + 'static) {
+		Python::with_gil(|py| self.set_stdin_with_gil(py, reader));
+	}
+
+	/// Feed `sys.stdin` from a Rust reader while this context runs Python code.
+	///
+	/// This function panics if the Python stream can't be created.
+	pub fn set_stdin_with_gil(&self, py: Python<'_>, reader: impl Read + Send + 'static) {
+		if let Err(e) = self.try_set_stdin_with_gil(py, reader) {
+			e.print(py);
+			panic!("Unable to set sys.stdin for the context");
+		}
+	}
+
+	/// Feed `sys.stdin` from a Rust reader while this context runs Python code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_set_stdin_with_gil`] instead.
+	pub fn try_set_stdin(&self, reader: impl Read + Send + 'static) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_set_stdin_with_gil(py, reader))
+	}
+
+	/// Feed `sys.stdin` from a Rust reader while this context runs Python code.
+	pub fn try_set_stdin_with_gil(&self, py: Python<'_>, reader: impl Read + Send + 'static) -> Result<(), InlinePythonError> {
+		self.set_stream(py, "stdin", new_reader(py, reader))
+	}
+
+	/// Forward records of Python's `logging` module to the [`log`] crate while this context runs Python code.
+	///
+	/// Records are forwarded while running code with [`Context::run`] and the
+	/// other `run` and `eval` methods, in the thread (or `asyncio` task) that runs the code.
+	/// See [`forward_logging`](crate::forward_logging) for how records are forwarded,
+	/// and to forward the logging of all Python code instead.
+	///
+	/// Calling this again has no effect.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::forward_logging_with_gil`] instead.
+	///
+	/// This function panics if the handler can't be created.
+	#[cfg(feature = "log")]
+	pub fn forward_logging(&self) {
+		Python::with_gil(|py| self.forward_logging_with_gil(py));
+	}
+
+	/// Forward records of Python's `logging` module to the [`log`] crate while this context runs Python code.
+	///
+	/// This function panics if the handler can't be created.
+	#[cfg(feature = "log")]
+	pub fn forward_logging_with_gil(&self, py: Python<'_>) {
+		if let Err(e) = self.try_forward_logging_with_gil(py) {
+			e.print(py);
+			panic!("Unable to forward Python logging for the context");
+		}
+	}
+
+	/// Forward records of Python's `logging` module to the [`log`] crate while this context runs Python code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_forward_logging_with_gil`] instead.
+	#[cfg(feature = "log")]
+	pub fn try_forward_logging(&self) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_forward_logging_with_gil(py))
+	}
+
+	/// Forward records of Python's `logging` module to the [`log`] crate while this context runs Python code.
+	#[cfg(feature = "log")]
+	pub fn try_forward_logging_with_gil(&self, py: Python<'_>) -> Result<(), InlinePythonError> {
+		crate::logging::handler_module(py).map_err(|e| InlinePythonError::new(py, e))?;
+		self.forward_logging.store(true, std::sync::atomic::Ordering::Relaxed);
+		Ok(())
+	}
+
+	/// Set a function to be called for every Python warning issued while this context runs Python code.
+	///
+	/// Without a hook, warnings are printed to `sys.stderr`, and most warnings
+	/// from libraries, such as `DeprecationWarning`s, are ignored.
+	/// With a hook, every warning is passed to the hook instead, once per location and run.
+	/// Setting a new hook replaces the previous one.
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// # use std::sync::mpsc;
+	/// let (tx, rx) = mpsc::channel();
+	/// let c = Context::new();
+	/// c.on_warning(move |warning| tx.send(warning).unwrap());
+	/// c.run(python! {
+	///     import warnings
+	///     warnings.warn("old", DeprecationWarning)
+	/// });
+	/// let warning = rx.recv().unwrap();
+	/// assert_eq!(warning.category(), "DeprecationWarning");
+	/// assert_eq!(warning.message(), "old");
+	/// ```
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::on_warning_with_gil`] instead.
+	///
+	/// This function panics if the hook can't be created.
+	pub fn on_warning(&self, hook: impl Fn(PyWarning) + Send + 'static) {
+		Python::with_gil(|py| self.on_warning_with_gil(py, hook));
+	}
+
+	/// Set a function to be called for every Python warning issued while this context runs Python code.
+	///
+	/// See [`Context::on_warning`].
+	///
+	/// This function panics if the hook can't be created.
+	pub fn on_warning_with_gil(&self, py: Python<'_>, hook: impl Fn(PyWarning) + Send + 'static) {
+		if let Err(e) = self.try_on_warning_with_gil(py, hook) {
+			e.print(py);
+			panic!("Unable to set the warning hook of the context");
+		}
+	}
+
+	/// Set a function to be called for every Python warning issued while this context runs Python code.
+	///
+	/// See [`Context::on_warning`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_on_warning_with_gil`] instead.
+	pub fn try_on_warning(&self, hook: impl Fn(PyWarning) + Send + 'static) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_on_warning_with_gil(py, hook))
+	}
+
+	/// Set a function to be called for every Python warning issued while this context runs Python code.
+	///
+	/// See [`Context::on_warning`].
+	pub fn try_on_warning_with_gil(&self, py: Python<'_>, hook: impl Fn(PyWarning) + Send + 'static) -> Result<(), InlinePythonError> {
+		new_hook(py, hook)
+			.and_then(|hook| self.warnings.as_ref(py).set_item("hook", hook))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Turn Python warnings of a category into errors while this context runs Python code.
+	///
+	/// The category is the name of a warning class, such as `DeprecationWarning`,
+	/// or the name of a module followed by the name of the class, such as
+	/// `numpy.VisibleDeprecationWarning`. Subclasses of the category are included.
+	///
+	/// A warning of this category raises an exception instead, such that for example
+	/// [`Context::try_run`] returns an error. The exception has the type of the category.
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	/// c.error_on_warning("DeprecationWarning");
+	/// let error = c.try_run(python! {
+	///     import warnings
+	///     warnings.warn("old", DeprecationWarning)
+	/// }).unwrap_err();
+	/// assert_eq!(error.type_name(), "DeprecationWarning");
+	/// ```
+	///
+	/// A category that doesn't exist is reported as an error when running code.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::error_on_warning_with_gil`] instead.
+	///
+	/// This function panics if the category can't be stored.
+	pub fn error_on_warning(&self, category: &str) {
+		Python::with_gil(|py| self.error_on_warning_with_gil(py, category));
+	}
+
+	/// Turn Python warnings of a category into errors while this context runs Python code.
+	///
+	/// See [`Context::error_on_warning`].
+	///
+	/// This function panics if the category can't be stored.
+	pub fn error_on_warning_with_gil(&self, py: Python<'_>, category: &str) {
+		if let Err(e) = self.try_error_on_warning_with_gil(py, category) {
+			e.print(py);
+			panic!("Unable to set the warning policy of the context");
+		}
+	}
+
+	/// Turn Python warnings of a category into errors while this context runs Python code.
+	///
+	/// See [`Context::error_on_warning`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_error_on_warning_with_gil`] instead.
+	pub fn try_error_on_warning(&self, category: &str) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_error_on_warning_with_gil(py, category))
+	}
+
+	/// Turn Python warnings of a category into errors while this context runs Python code.
+	///
+	/// See [`Context::error_on_warning`].
+	pub fn try_error_on_warning_with_gil(&self, py: Python<'_>, category: &str) -> Result<(), InlinePythonError> {
+		let warnings = self.warnings.as_ref(py);
+		(|| -> PyResult<()> {
+			match warnings.get_item("errors") {
+				Some(errors) => errors.downcast::<PyList>()?.append(category),
+				None => warnings.set_item("errors", [category]),
+			}
+		})()
+		.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	fn set_stream(&self, py: Python<'_>, name: &str, stream: PyResult<PyObject>) -> Result<(), InlinePythonError> {
+		stream
+			.and_then(|stream| self.streams.as_ref(py).set_item(name, stream))
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Run Python code using this context.
+	///
+	/// This function should be called using the `python!{}` macro:
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	///
+	/// c.run(python!{
+	///     print("Hello World")
+	/// });
+	/// ```
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::run_with_gil`] instead.
+	///
+	/// This function panics if the Python code fails.
+	pub fn run<F: SetVariables>(&self, code: PythonBlock<F>) {
+		Python::with_gil(|py| self.run_with_gil(py, code));
+	}
+
+	/// Run Python code using this context.
+	///
+	/// This function should be called using the `python!{}` macro, just like
+	/// [`Context::run`].
+	///
+	/// This function panics if the Python code fails.
+	pub fn run_with_gil<F: SetVariables>(&self, py: Python<'_>, code: PythonBlock<F>) {
+		match self.try_run_with_gil(py, code) {
+			Ok(()) => (),
+			Err(e) => {
+				e.print(py);
+				panic!("{}", "python!{...} failed to execute");
+			}
+		}
+	}
+
+	/// Run Python code using this context.
+	///
+	/// This function should be called using the `python!{}` macro, just like
+	/// [`Context::run`]:
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	///
+	/// let error = c.try_run(python!{
+	///     raise ValueError("oops")
+	/// }).unwrap_err();
+	///
+	/// assert_eq!(error.type_name(), "ValueError");
+	/// assert_eq!(error.message(), "oops");
+	/// ```
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_run_with_gil`] instead.
+	pub fn try_run<F: SetVariables>(&self, code: PythonBlock<F>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_run_with_gil(py, code))
+	}
+
+	/// Run Python code using this context.
+	///
+	/// This function should be called using the `python!{}` macro, just like
+	/// [`Context::try_run`].
+	pub fn try_run_with_gil<F: SetVariables>(&self, py: Python<'_>, code: PythonBlock<F>) -> Result<(), InlinePythonError> {
+		let embedded = code.embedded;
+		code.set_variables
+			.set_variables(self.globals(py))
+			.and_then(|write_back| {
+				run_python_code(py, self, embedded)?;
+				write_back(self.globals(py))
+			})
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Run Python code using this context, and capture its output.
+	///
+	/// This function should be called using the `python!{}` macro:
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	///
+	/// let output = c.run_capturing(python! {
+	///     import sys
+	///     print("hello")
+	///     print("oops", file=sys.stderr)
+	/// });
+	///
+	/// assert_eq!(output.stdout, "hello\n");
+	/// assert_eq!(output.stderr, "oops\n");
+	/// ```
+	///
+	/// While the code runs, `sys.stdout` and `sys.stderr` are replaced,
+	/// including any streams set with [`Context::set_stdout`] or [`Context::set_stderr`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::run_capturing_with_gil`] instead.
+	///
+	/// This function panics if the Python code fails.
+	pub fn run_capturing<F: SetVariables>(&self, code: PythonBlock<F>) -> CapturedOutput {
+		Python::with_gil(|py| self.run_capturing_with_gil(py, code))
+	}
+
+	/// Run Python code using this context, and capture its output.
+	///
+	/// See [`Context::run_capturing`].
+	///
+	/// This function panics if the Python code fails.
+	pub fn run_capturing_with_gil<F: SetVariables>(&self, py: Python<'_>, code: PythonBlock<F>) -> CapturedOutput {
+		match self.try_run_capturing_with_gil(py, code) {
+			Ok(output) => output,
+			Err(e) => {
+				e.print(py);
+				panic!("{}", "python!{...} failed to execute");
+			}
+		}
+	}
+
+	/// Run Python code using this context, and capture its output.
+	///
+	/// See [`Context::run_capturing`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_run_capturing_with_gil`] instead.
+	pub fn try_run_capturing<F: SetVariables>(&self, code: PythonBlock<F>) -> Result<CapturedOutput, InlinePythonError> {
+		Python::with_gil(|py| self.try_run_capturing_with_gil(py, code))
+	}
+
+	/// Run Python code using this context, and capture its output.
+	///
+	/// See [`Context::run_capturing`].
+	pub fn try_run_capturing_with_gil<F: SetVariables>(
+		&self,
+		py: Python<'_>,
+		code: PythonBlock<F>,
+	) -> Result<CapturedOutput, InlinePythonError> {
+		let streams = self.streams.as_ref(py);
+		let (original, stdout, stderr) = (|| -> PyResult<_> {
+			let string_io = py.import("io")?.getattr("StringIO")?;
+			let (stdout, stderr) = (string_io.call0()?, string_io.call0()?);
+			let original = streams.copy()?;
+			streams.set_item("stdout", stdout)?;
+			streams.set_item("stderr", stderr)?;
+			Ok((original, stdout, stderr))
+		})()
+		.map_err(|e| InlinePythonError::new(py, e))?;
+		let result = self.try_run_with_gil(py, code);
+		streams.clear();
+		let output = streams.update(original.as_mapping()).and_then(|()| {
+			Ok(CapturedOutput {
+				stdout: stdout.call_method0("getvalue")?.extract()?,
+				stderr: stderr.call_method0("getvalue")?.extract()?,
+			})
+		});
+		result?;
+		output.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Run Python code that uses `await` using this context.
+	///
+	/// This function should be called using the `python!{}` macro:
+	///
+	/// ```
+	/// # use inline_python::{Context, python};
+	/// # async fn example() {
+	/// let c = Context::new();
+	///
+	/// c.run_async(python! {
+	///     import asyncio
+	///     await asyncio.sleep(0.1)
+	///     data = "done"
+	/// }).await;
+	///
+	/// assert_eq!(c.get::<String>("data"), "done");
+	/// # }
+	/// ```
+	///
+	/// A block that uses `await` outside of a function can't be run
+	/// with [`Context::run`] or used as a statement; it must be run with this function.
+	/// Anything else is a compile error:
+	///
+	/// ```compile_fail
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	/// c.run(python! {
+	///     import asyncio
+	///     await asyncio.sleep(0.1)
+	/// });
+	/// ```
+	///
+	/// Since the future can be leaked without being dropped, a block that uses `await`
+	/// can't lend Rust values with `'borrow` or `'buffer`, which is also a compile error:
+	///
+	/// ```compile_fail
+	/// # use inline_python::{Context, python};
+	/// let c = Context::new();
+	/// let values = vec![1, 2, 3];
+	/// c.run_async(python! {
+	///     import asyncio
+	///     await asyncio.sleep(0.1)
+	///     print(len('borrow values))
+	/// });
+	/// ```
+	///
+	/// The coroutine of the block runs on an `asyncio` event loop, in a
+	/// background thread that is started the first time this is used.
+	/// The returned future completes when the block is done, and works with any Rust async runtime,
+	/// without blocking the thread that polls it while the block is waiting.
+	/// Dropping the future before it completes cancels the coroutine,
+	/// which raises `asyncio.CancelledError` at the `await` it is waiting at.
+	///
+	/// The streams, warning settings and logging handler of the context are
+	/// applied whenever the code of the block is running, but not while it is waiting.
+	///
+	/// Several blocks can run in the same context at the same time, interleaved at their
+	/// `await`s. They share the global variables of the context, but each gets its own result.
+	///
+	/// This function panics if the Python code fails.
+	pub async fn run_async<F: SetVariables>(&self, code: AsyncPythonBlock<F>) {
+		if let Err(e) = self.try_run_async(code).await {
+			Python::with_gil(|py| e.print(py));
+			panic!("{}", "python!{...} failed to execute");
+		}
+	}
+
+	/// Run Python code that uses `await` using this context.
+	///
+	/// See [`Context::run_async`].
+	pub async fn try_run_async<F: SetVariables>(&self, code: AsyncPythonBlock<F>) -> Result<(), InlinePythonError> {
+		let (task, write_back) = Python::with_gil(|py| {
+			let embedded = code.embedded;
+			code.set_variables
+				.set_variables(self.globals(py))
+				.and_then(|write_back| Ok((start_python_code(py, self, embedded)?, write_back)))
+				.map_err(|e| InlinePythonError::new(py, e))
+		})?;
+		let result = match task {
+			Some(task) => task.await.map(drop),
+			None => Ok(()),
+		};
+		Python::with_gil(|py| {
+			result
+				.and_then(|()| write_back(self.globals(py)))
+				.map_err(|e| InlinePythonError::new(py, e))
+		})
+	}
+
+	/// Run Python source code using this context.
+	///
+	/// Unlike the `python!{}` macro, this compiles the code at runtime,
+	/// which is useful for code that isn't known at compile time, such as plugin scripts.
+	/// The `filename` is only used in error messages and tracebacks.
+	///
+	/// ```
+	/// # use inline_python::Context;
+	/// let c = Context::new();
+	///
+	/// c.run_source("foo = 5", "<config>");
+	///
+	/// assert_eq!(c.get::<i32>("foo"), 5);
+	/// ```
+	///
+	/// This function panics if the Python code fails.
+	/// Use [`Context::try_run_source`] to handle the error instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::run_source_with_gil`] instead.
+	pub fn run_source(&self, code: &str, filename: &str) {
+		Python::with_gil(|py| self.run_source_with_gil(py, code, filename));
+	}
+
+	/// Run Python source code using this context.
+	///
+	/// See [`Context::run_source`].
+	///
+	/// This function panics if the Python code fails.
+	pub fn run_source_with_gil(&self, py: Python<'_>, code: &str, filename: &str) {
+		if let Err(e) = self.try_run_source_with_gil(py, code, filename) {
+			e.print(py);
+			panic!("Python code in `{}` failed to execute", filename);
+		}
+	}
+
+	/// Run Python source code using this context.
+	///
+	/// See [`Context::run_source`].
+	///
+	/// ```
+	/// # use inline_python::Context;
+	/// let c = Context::new();
+	///
+	/// let error = c.try_run_source("1 / 0", "<input>").unwrap_err();
+	///
+	/// assert_eq!(error.type_name(), "ZeroDivisionError");
+	/// ```
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_run_source_with_gil`] instead.
+	pub fn try_run_source(&self, code: &str, filename: &str) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_run_source_with_gil(py, code, filename))
+	}
+
+	/// Run Python source code using this context.
+	///
+	/// See [`Context::try_run_source`].
+	pub fn try_run_source_with_gil(&self, py: Python<'_>, code: &str, filename: &str) -> Result<(), InlinePythonError> {
+		self.try_eval_source_with_gil::<&PyAny>(py, code, filename).map(|_| ())
+	}
+
+	/// Run Python source code using this context, and get its result.
+	///
+	/// The result is the value of the final expression, or `None` if the code doesn't end in an expression.
+	/// Unlike in a `python!{}` block, `return` and `await` outside of a function
+	/// are syntax errors, just like in any other Python script.
+	///
+	/// ```
+	/// # use inline_python::Context;
+	/// let c = Context::new();
+	///
+	/// let x: i32 = c.eval_source("a = 2\na * 3", "<input>");
+	///
+	/// assert_eq!(x, 6);
+	/// ```
+	///
+	/// This function panics if the Python code fails, or the conversion fails.
+	/// Use [`Context::try_eval_source`] to handle the error instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::eval_source_with_gil`] instead.
+	pub fn eval_source<T: for<'p> FromPyObject<'p>>(&self, code: &str, filename: &str) -> T {
+		Python::with_gil(|py| self.eval_source_with_gil(py, code, filename))
+	}
+
+	/// Run Python source code using this context, and get its result.
+	///
+	/// See [`Context::eval_source`].
+	///
+	/// This function panics if the Python code fails, or the conversion fails.
+	pub fn eval_source_with_gil<'p, T: FromPyObject<'p>>(&'p self, py: Python<'p>, code: &str, filename: &str) -> T {
+		match self.try_eval_source_with_gil(py, code, filename) {
+			Ok(value) => value,
+			Err(e) => {
+				e.print(py);
+				panic!("Python code in `{}` failed to execute", filename);
+			}
+		}
+	}
+
+	/// Run Python source code using this context, and get its result.
+	///
+	/// See [`Context::eval_source`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_eval_source_with_gil`] instead.
+	pub fn try_eval_source<T: for<'p> FromPyObject<'p>>(&self, code: &str, filename: &str) -> Result<T, InlinePythonError> {
+		Python::with_gil(|py| self.try_eval_source_with_gil(py, code, filename))
+	}
+
+	/// Run Python source code using this context, and get its result.
+	///
+	/// See [`Context::try_eval_source`].
+	pub fn try_eval_source_with_gil<'p, T: FromPyObject<'p>>(
+		&'p self,
+		py: Python<'p>,
+		code: &str,
+		filename: &str,
+	) -> Result<T, InlinePythonError> {
+		compile_source(py, code, filename)
+			.and_then(|code| run_code_object(py, self, &code))
+			.and_then(FromPyObject::extract)
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
+
+	/// Run a Python file using this context.
+	///
+	/// The compiled code is cached, and only compiled again when the
+	/// modification time of the file changes.
+	///
+	/// This function panics if the file can't be read, or the Python code fails.
+	/// Use [`Context::try_run_file`] to handle the error instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::run_file_with_gil`] instead.
+	pub fn run_file(&self, path: impl AsRef<Path>) {
+		Python::with_gil(|py| self.run_file_with_gil(py, path));
+	}
+
+	/// Run a Python file using this context.
+	///
+	/// See [`Context::run_file`].
+	///
+	/// This function panics if the file can't be read, or the Python code fails.
+	pub fn run_file_with_gil(&self, py: Python<'_>, path: impl AsRef<Path>) {
+		let path = path.as_ref();
+		if let Err(e) = self.try_run_file_with_gil(py, path) {
+			e.print(py);
+			panic!("Python file `{}` failed to execute", path.display());
+		}
+	}
+
+	/// Run a Python file using this context.
+	///
+	/// See [`Context::run_file`].
+	///
+	/// Failing to read the file is reported as a Python `OSError`,
+	/// such as `FileNotFoundError`.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_run_file_with_gil`] instead.
+	pub fn try_run_file(&self, path: impl AsRef<Path>) -> Result<(), InlinePythonError> {
+		Python::with_gil(|py| self.try_run_file_with_gil(py, path))
+	}
+
+	/// Run a Python file using this context.
+	///
+	/// See [`Context::try_run_file`].
+	pub fn try_run_file_with_gil(&self, py: Python<'_>, path: impl AsRef<Path>) -> Result<(), InlinePythonError> {
+		self.try_eval_file_with_gil::<&PyAny>(py, path).map(|_| ())
+	}
+
+	/// Run a Python file using this context, and get its result.
+	///
+	/// See [`Context::run_file`] and [`Context::eval_source`].
+	///
+	/// This function panics if the file can't be read, the Python code fails, or the conversion fails.
+	/// Use [`Context::try_eval_file`] to handle the error instead.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::eval_file_with_gil`] instead.
+	pub fn eval_file<T: for<'p> FromPyObject<'p>>(&self, path: impl AsRef<Path>) -> T {
+		Python::with_gil(|py| self.eval_file_with_gil(py, path))
+	}
+
+	/// Run a Python file using this context, and get its result.
+	///
+	/// See [`Context::eval_file`].
+	///
+	/// This function panics if the file can't be read, the Python code fails, or the conversion fails.
+	pub fn eval_file_with_gil<'p, T: FromPyObject<'p>>(&'p self, py: Python<'p>, path: impl AsRef<Path>) -> T {
+		let path = path.as_ref();
+		match self.try_eval_file_with_gil(py, path) {
+			Ok(value) => value,
+			Err(e) => {
+				e.print(py);
+				panic!("Python file `{}` failed to execute", path.display());
+			}
+		}
+	}
+
+	/// Run a Python file using this context, and get its result.
+	///
+	/// See [`Context::try_run_file`] and [`Context::eval_source`].
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`Context::try_eval_file_with_gil`] instead.
+	pub fn try_eval_file<T: for<'p> FromPyObject<'p>>(&self, path: impl AsRef<Path>) -> Result<T, InlinePythonError> {
+		Python::with_gil(|py| self.try_eval_file_with_gil(py, path))
+	}
+
+	/// Run a Python file using this context, and get its result.
+	///
+	/// See [`Context::try_eval_file`].
+	pub fn try_eval_file_with_gil<'p, T: FromPyObject<'p>>(
+		&'p self,
+		py: Python<'p>,
+		path: impl AsRef<Path>,
+	) -> Result<T, InlinePythonError> {
+		compile_file(py, path.as_ref())
+			.and_then(|code| run_code_object(py, self, &code))
+			.and_then(FromPyObject::extract)
+			.map_err(|e| InlinePythonError::new(py, e))
+	}
 }
@@ -0,0 +1,131 @@
+use crate::{Context, InlinePythonError};
+use pyo3::{
+	exceptions::{PyNameError, PyTypeError},
+	types::PyDict,
+	FromPyObject, PyErr, Python,
+};
+
+/// A type that can be extracted from the global variables of a [`Context`].
+///
+/// This can be derived for structs with named fields, where each field is
+/// extracted from the global variable with the same name:
+///
+/// ```
+/// # use inline_python::{python, Context, FromContext};
+/// #[derive(FromContext)]
+/// struct Out {
+///     mean: f64,
+///     #[context(name = "names")]
+///     labels: Vec<String>,
+/// }
+///
+/// let c: Context = python! {
+///     mean = 1.5
+///     names = ["a", "b"]
+/// };
+///
+/// let out: Out = c.extract();
+/// assert_eq!(out.mean, 1.5);
+/// assert_eq!(out.labels, ["a", "b"]);
+/// ```
+///
+/// See [`Context::extract`].
+pub trait FromContext: Sized {
+	/// Extract a value from the global variables of the context.
+	///
+	/// All missing and mistyped variables are reported together in one error.
+	fn from_context(py: Python<'_>, context: &Context) -> Result<Self, InlinePythonError>;
+}
+
+/// A type that can be stored as global variables in a [`Context`].
+///
+/// This can be derived for structs with named fields, where each field is
+/// stored in the global variable with the same name:
+///
+/// ```
+/// # use inline_python::{python, Context, IntoContext};
+/// #[derive(IntoContext)]
+/// struct Config {
+///     threshold: f64,
+///     #[context(name = "names")]
+///     labels: Vec<String>,
+/// }
+///
+/// let c = Context::new();
+/// c.set_all(Config { threshold: 0.5, labels: vec!["a".into()] });
+///
+/// c.run(python! {
+///     assert threshold == 0.5
+///     assert names == ["a"]
+/// });
+/// ```
+///
+/// See [`Context::set_all`].
+pub trait IntoContext {
+	/// Store this value in the global variables of the context.
+	fn into_context(self, py: Python<'_>, context: &Context) -> Result<(), InlinePythonError>;
+}
+
+/// Extracts the fields of a `#[derive(FromContext)]` struct, collecting all errors.
+#[doc(hidden)]
+pub struct ContextFields<'p> {
+	py: Python<'p>,
+	globals: &'p PyDict,
+	errors: Vec<PyErr>,
+}
+
+impl<'p> ContextFields<'p> {
+	pub fn new(py: Python<'p>, context: &'p Context) -> Self {
+		Self {
+			py,
+			globals: context.globals(py),
+			errors: Vec::new(),
+		}
+	}
+
+	/// Extract a global variable, or remember the error and return `None`.
+	pub fn get<T: for<'a> FromPyObject<'a>>(&mut self, name: &str) -> Option<T> {
+		let value = match self.globals.get_item(name) {
+			Some(value) => value,
+			None => {
+				self.errors.push(PyNameError::new_err(format!("name '{}' is not defined", name)));
+				return None;
+			}
+		};
+		match value.extract() {
+			Ok(value) => Some(value),
+			Err(e) => {
+				let error = PyTypeError::new_err(format!(
+					"unable to convert '{}' to `{}`: {}",
+					name,
+					std::any::type_name::<T>(),
+					e.value(self.py),
+				));
+				error.set_cause(self.py, Some(e));
+				self.errors.push(error);
+				None
+			}
+		}
+	}
+
+	/// Report all errors that occurred, if any.
+	///
+	/// A single error is returned as is.
+	/// Multiple errors are combined into one `TypeError`.
+	pub fn finish<T>(mut self) -> Result<(), InlinePythonError> {
+		let error = match self.errors.len() {
+			0 => return Ok(()),
+			1 => self.errors.remove(0),
+			n => {
+				let messages: Vec<String> = self.errors.iter().map(|e| e.value(self.py).to_string()).collect();
+				PyTypeError::new_err(format!(
+					"unable to extract `{}` from the context ({} errors): {}",
+					std::any::type_name::<T>(),
+					n,
+					messages.join("; "),
+				))
+			}
+		};
+		Err(InlinePythonError::new(self.py, error))
+	}
+}
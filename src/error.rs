@@ -0,0 +1,106 @@
+use pyo3::{PyErr, Python};
+use std::fmt;
+
+/// An error raised by Python code, or while converting values to or from Python.
+///
+/// This is returned by the fallible methods of [`Context`](crate::Context),
+/// such as [`Context::try_run`](crate::Context::try_run).
+///
+/// The type name, message and traceback of the Python exception are captured
+/// when the error is created, so they can be inspected without the GIL.
+/// The original [`PyErr`] is available through [`InlinePythonError::py_err`].
+pub struct InlinePythonError {
+	type_name: String,
+	message: String,
+	traceback: Option<String>,
+	error: PyErr,
+}
+
+impl InlinePythonError {
+	/// Capture the details of a Python exception.
+	pub fn new(py: Python, error: PyErr) -> Self {
+		let type_name = match error.get_type(py).name() {
+			Ok(name) => name.to_string(),
+			Err(_) => String::from("<unknown>"),
+		};
+		let message = match error.value(py).str() {
+			Ok(message) => message.to_string_lossy().into_owned(),
+			Err(_) => String::new(),
+		};
+		let traceback = error.traceback(py).and_then(|tb| tb.format().ok());
+		Self {
+			type_name,
+			message,
+			traceback,
+			error,
+		}
+	}
+
+	/// The name of the Python exception type, such as `ValueError`.
+	pub fn type_name(&self) -> &str {
+		&self.type_name
+	}
+
+	/// The message of the Python exception, as given by `str(exception)`.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+
+	/// The formatted traceback of the Python exception, if there is one.
+	pub fn traceback(&self) -> Option<&str> {
+		self.traceback.as_deref()
+	}
+
+	/// The original Python exception.
+	pub fn py_err(&self) -> &PyErr {
+		&self.error
+	}
+
+	/// Convert this into the original Python exception.
+	pub fn into_py_err(self) -> PyErr {
+		self.error
+	}
+
+	/// Print the Python exception and its traceback to `sys.stderr`.
+	pub fn print(&self, py: Python) {
+		self.error.print(py);
+	}
+}
+
+impl From<PyErr> for InlinePythonError {
+	fn from(error: PyErr) -> Self {
+		Python::with_gil(|py| Self::new(py, error))
+	}
+}
+
+impl From<InlinePythonError> for PyErr {
+	fn from(error: InlinePythonError) -> Self {
+		error.error
+	}
+}
+
+impl fmt::Debug for InlinePythonError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("InlinePythonError")
+			.field("type_name", &self.type_name)
+			.field("message", &self.message)
+			.field("traceback", &self.traceback)
+			.finish()
+	}
+}
+
+impl fmt::Display for InlinePythonError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.message.is_empty() {
+			write!(f, "{}", self.type_name)
+		} else {
+			write!(f, "{}: {}", self.type_name, self.message)
+		}
+	}
+}
+
+impl std::error::Error for InlinePythonError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
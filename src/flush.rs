@@ -1,6 +1,64 @@
-// extern "C" {
-// 	fn fflush(_: *mut std::ffi::c_void);
-// 	static mut stdout: *mut std::ffi::c_void;
-// }
+//! Keeping the output of Rust and Python in order.
+//!
+//! Rust, Python and C each have their own buffer for stdout.
+//! When stdout is not a terminal, Python only writes its buffer when it is full,
+//! so without flushing, the output of a block would show up long after the Rust
+//! output that was printed after it, or not at all if the process exits first.
 
-pub fn flush() {}
+use pyo3::Python;
+use std::io::Write;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+extern "C" {
+	fn fflush(stream: *mut c_void) -> c_int;
+}
+
+static FLUSH_OUTPUT: AtomicBool = AtomicBool::new(true);
+
+/// Set whether stdout and stderr are flushed around running Python code.
+///
+/// By default, the stdout buffers of Rust and C are flushed before a
+/// `python!{}` block or other Python code runs, and Python's `sys.stdout` and
+/// `sys.stderr` (and C's buffers) are flushed after it, such that the output of
+/// Rust and Python shows up in the order it was printed, even when stdout is a
+/// pipe or a file.
+///
+/// Disabling this avoids the cost of flushing for code that runs often and
+/// doesn't print, at the cost of output possibly showing up out of order.
+///
+/// ```
+/// inline_python::set_flush_output(false);
+/// ```
+pub fn set_flush_output(enabled: bool) {
+	FLUSH_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Flush the output of Rust and C, before running Python code.
+pub(crate) fn flush_before() {
+	if FLUSH_OUTPUT.load(Ordering::Relaxed) {
+		let _ = std::io::stdout().flush();
+		flush_c();
+	}
+}
+
+/// Flush the output of Python and C, after running Python code.
+///
+/// Failures are ignored, since `sys.stdout` might for example be `None`.
+pub(crate) fn flush_after(py: Python<'_>) {
+	if FLUSH_OUTPUT.load(Ordering::Relaxed) {
+		if let Ok(sys) = py.import("sys") {
+			for name in ["stdout", "stderr"].iter() {
+				let _ = sys.getattr(*name).and_then(|s| s.call_method0("flush"));
+			}
+		}
+		flush_c();
+	}
+}
+
+fn flush_c() {
+	// Flushing a null stream flushes all of C's output streams.
+	unsafe {
+		fflush(std::ptr::null_mut());
+	}
+}
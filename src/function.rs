@@ -0,0 +1,89 @@
+use pyo3::{
+	exceptions::PyTypeError,
+	types::{PyCFunction, PyDict, PyTuple},
+	FromPyObject, IntoPy, PyErr, PyObject, PyResult, Python,
+};
+use std::marker::PhantomData;
+
+/// A Rust closure that can be called from Python.
+///
+/// This is implemented for closures with up to eight arguments that all
+/// implement [`FromPyObject`], which return a value that can be converted to a
+/// Python object, or a `Result` of such a value with an error that can be
+/// converted to a [`PyErr`](pyo3::PyErr).
+///
+/// See [`Context::add_fn`](crate::Context::add_fn).
+pub trait IntoPyFunction<Args>: Send + 'static {
+	/// Create a Python function that calls this closure.
+	fn into_py_function(self, py: Python<'_>) -> PyResult<&PyCFunction>;
+}
+
+/// Marks the arguments of closures that return a `Result`, for [`IntoPyFunction`].
+///
+/// This keeps the implementations for closures that return a `Result` apart
+/// from those for closures that return a value directly.
+#[doc(hidden)]
+pub struct Fallible<Args>(PhantomData<Args>);
+
+/// Check that no keyword arguments were given, and the right number of positional arguments.
+fn check_arguments(args: &PyTuple, kwargs: Option<&PyDict>, n: usize) -> PyResult<()> {
+	if kwargs.is_some_and(|kwargs| !kwargs.is_empty()) {
+		return Err(PyTypeError::new_err("function takes no keyword arguments"));
+	}
+	if args.len() != n {
+		return Err(PyTypeError::new_err(format!(
+			"function takes {} positional argument{} but {} {} given",
+			n,
+			if n == 1 { "" } else { "s" },
+			args.len(),
+			if args.len() == 1 { "was" } else { "were" },
+		)));
+	}
+	Ok(())
+}
+
+macro_rules! impl_into_py_function {
+	($n:literal $(, $arg:ident $i:literal)*) => {
+		impl<F, R, $($arg,)*> IntoPyFunction<($($arg,)*)> for F
+		where
+			F: Fn($($arg),*) -> R + Send + 'static,
+			R: IntoPy<PyObject>,
+			$($arg: for<'p> FromPyObject<'p>,)*
+		{
+			fn into_py_function(self, py: Python<'_>) -> PyResult<&PyCFunction> {
+				PyCFunction::new_closure(py, None, None, move |args: &PyTuple, kwargs: Option<&PyDict>| -> PyResult<PyObject> {
+					check_arguments(args, kwargs, $n)?;
+					Ok(self($(args.get_item($i)?.extract::<$arg>()?),*).into_py(args.py()))
+				})
+			}
+		}
+
+		impl<F, R, Error, $($arg,)*> IntoPyFunction<Fallible<($($arg,)*)>> for F
+		where
+			F: Fn($($arg),*) -> Result<R, Error> + Send + 'static,
+			R: IntoPy<PyObject>,
+			Error: Into<PyErr>,
+			$($arg: for<'p> FromPyObject<'p>,)*
+		{
+			fn into_py_function(self, py: Python<'_>) -> PyResult<&PyCFunction> {
+				PyCFunction::new_closure(py, None, None, move |args: &PyTuple, kwargs: Option<&PyDict>| -> PyResult<PyObject> {
+					check_arguments(args, kwargs, $n)?;
+					match self($(args.get_item($i)?.extract::<$arg>()?),*) {
+						Ok(value) => Ok(value.into_py(args.py())),
+						Err(e) => Err(e.into()),
+					}
+				})
+			}
+		}
+	};
+}
+
+impl_into_py_function!(0);
+impl_into_py_function!(1, A 0);
+impl_into_py_function!(2, A 0, B 1);
+impl_into_py_function!(3, A 0, B 1, C 2);
+impl_into_py_function!(4, A 0, B 1, C 2, D 3);
+impl_into_py_function!(5, A 0, B 1, C 2, D 3, E 4);
+impl_into_py_function!(6, A 0, B 1, C 2, D 3, E 4, G 5);
+impl_into_py_function!(7, A 0, B 1, C 2, D 3, E 4, G 5, H 6);
+impl_into_py_function!(8, A 0, B 1, C 2, D 3, E 4, G 5, H 6, I 7);
@@ -18,14 +18,56 @@
 //!
 //! Use the `python!{..}` macro to write Python code directly in your Rust code.
 //!
-//! _NOTE:_ Rust **nightly** toolchain is required. Feature `proc_macro_span` is still unstable,
-//! for more details check out [issue #54725](https://github.com/rust-lang/rust/issues/54725) - Tracking issue for `proc_macro::Span` inspection APIs
+//! _NOTE:_ Rust 1.88 or later is required, since the macro uses the line and
+//! column information of the tokens to reconstruct the Python code.
+//! On a **nightly** toolchain, Python syntax errors are highlighted across the
+//! entire line, instead of just on its first token.
 //!
 //! ## Using Rust variables
 //!
 //! To reference Rust variables, use `'var`, as shown in the example above.
 //! `var` needs to implement [`pyo3::ToPyObject`].
 //!
+//! Other Rust expressions can be used with `'_(expr)` or `'_{ expr }`.
+//! Every such expression is evaluated once, before the Python code runs.
+//! (Note that `'var.field` accesses the Python attribute `field` of the
+//! converted `'var`, not the Rust field. Use `'_(var.field)` for that.)
+//!
+//! ```
+//! # use inline_python::python;
+//! let items = vec![1, 2, 3];
+//! python! {
+//!     assert '_(items.len()) == 3
+//!     assert '_{ items.iter().sum::<i32>() } == sum('items)
+//! }
+//! ```
+//!
+//! To get changes made by the Python code back into a Rust variable, use `'mut var`.
+//! After the Python code finished, the final value of `'mut var` is converted back
+//! using [`pyo3::FromPyObject`] and assigned to `var`.
+//!
+//! ```
+//! # use inline_python::python;
+//! let mut names = vec!["a".to_string()];
+//! python! {
+//!     'mut names.append("b")
+//! }
+//! assert_eq!(names, ["a", "b"]);
+//! ```
+//!
+//! With the `serde` feature enabled, `'serde var` converts a value that
+//! implements `serde::Serialize` to native Python `dict`s, `list`s and scalars.
+//! See [`Serde`] for the details of the conversion, and
+//! [`Context::set_serde`] and [`Context::get_serde`] for the context equivalents.
+//!
+//! To let Python use a slice of numbers as a buffer, without converting every
+//! element to a Python object, use `'buffer var` or `'buffer mut var`. See [`SliceBuffer`].
+//!
+//! To lend a reference to a type that implements [`Borrowable`] to the block,
+//! without converting it, use `'borrow var` or `'borrow mut var`. See [`Borrowed`].
+//! A borrowed `Vec`, `HashMap` or `BTreeMap` is a live Python sequence or mapping,
+//! which reads from and writes to the Rust collection directly.
+//!
 //! ## Re-using a Python context
 //!
 //! It is possible to create a [`Context`] object ahead of time and use it for running the Python code.
@@ -59,6 +101,9 @@
 //! });
 //! ```
 //!
+//! A context can also run Python code that is only known at runtime, such as
+//! plugin scripts, using [`Context::run_source`] and [`Context::run_file`].
+//!
 //! ## Getting information back
 //!
 //! A [`Context`] object could also be used to pass information back to Rust,
@@ -74,6 +119,111 @@
 //! assert_eq!(c.get::<i32>("foo"), 5);
 //! ```
 //!
+//! To get several variables at once, derive [`FromContext`](trait@FromContext)
+//! for a struct and use [`Context::extract`]. Similarly,
+//! [`IntoContext`](trait@IntoContext) and [`Context::set_all`] set several
+//! variables at once.
+//!
+//! ## Getting the result of a block
+//!
+//! A `python!{}` block can also be assigned to a variable of a type like
+//! `i32`, `String`, `Vec<T>` or `Option<T>`, to run it in a new context and
+//! convert its result.
+//! The result of a block is the value of its final expression, or the value
+//! given to a `return` statement outside of any function.
+//! A block that contains such a `return` statement runs as the body of a
+//! function, so its variables are local to that block.
+//!
+//! ```
+//! # use inline_python::python;
+//! let n: i32 = python! {
+//!   x = 5
+//!   x * 2
+//! };
+//!
+//! let parity: String = python! {
+//!   if 'n % 2 == 0:
+//!     return "even"
+//!   return "odd"
+//! };
+//!
+//! assert_eq!(n, 10);
+//! assert_eq!(parity, "even");
+//! ```
+//!
+//! For any other type implementing [`pyo3::FromPyObject`], use [`Extract`].
+//!
+//! ## Capturing output
+//!
+//! [`Context::run_capturing`] runs a block and returns everything it printed
+//! to `sys.stdout` and `sys.stderr` as a [`CapturedOutput`].
+//! To route Python's output to any [`std::io::Write`] instead, use
+//! [`Context::set_stdout`] and [`Context::set_stderr`] for a single context,
+//! or [`set_stdout`] and [`set_stderr`] for all Python code.
+//! Similarly, [`Context::set_stdin`] and [`set_stdin`] feed `sys.stdin`
+//! and `input()` from any [`std::io::Read`].
+//!
+//! ```
+//! # use inline_python::{Context, python};
+//! let c = Context::new();
+//!
+//! let output = c.run_capturing(python! {
+//!   print("hello")
+//! });
+//!
+//! assert_eq!(output.stdout, "hello\n");
+//! ```
+//!
+//! Output of Rust and Python is flushed before and after running Python code,
+//! so it stays in order when stdout is a pipe or a file.
+//! See [`set_flush_output`] to turn that off.
+//!
+//! With the `log` feature enabled, [`forward_logging`] and
+//! [`Context::forward_logging`] forward records of Python's `logging` module
+//! to the [`log`](https://docs.rs/log) crate, and through that to `tracing`.
+//!
+//! ## Handling errors
+//!
+//! By default, a Python exception causes a panic.
+//! The `try_` variants of the [`Context`] methods, such as [`Context::try_run`] and
+//! [`Context::try_get`], return an [`InlinePythonError`] instead, which works with `?`.
+//!
+//! ```
+//! # use inline_python::{Context, InlinePythonError, python};
+//! # fn main() -> Result<(), InlinePythonError> {
+//! let c = Context::try_new()?;
+//!
+//! c.try_run(python! {
+//!   foo = 5
+//! })?;
+//!
+//! assert_eq!(c.try_get::<i32>("foo")?, 5);
+//! assert_eq!(c.try_get::<i32>("bar").unwrap_err().type_name(), "NameError");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Python warnings can be passed to a Rust function with [`Context::on_warning`],
+//! and [`Context::error_on_warning`] turns warnings of a category into errors.
+//!
+//! ## Async blocks
+//!
+//! A block that uses `await` outside of a function must be run with
+//! [`Context::run_async`], which returns a Rust future. The block runs on an
+//! `asyncio` event loop in a background thread, so the future works with any
+//! Rust async runtime. Dropping the future cancels the block.
+//!
+//! ```
+//! # use inline_python::{Context, python};
+//! # async fn example() {
+//! let c = Context::new();
+//! c.run_async(python! {
+//!   import asyncio
+//!   await asyncio.sleep(0.1)
+//! }).await;
+//! # }
+//! ```
+//!
 //! ## Syntax issues
 //!
 //! Since the Rust tokenizer will tokenize the Python code, some valid Python
@@ -110,18 +260,97 @@
 //!   to `//`.
 //!
 //! Everything else should work fine.
+//!
+//! ## Verbatim mode
+//!
+//! Starting a block with `#![verbatim]` makes the macro use the original
+//! source text of the block, instead of reconstructing the Python code from
+//! the Rust tokens. Only `'var` interpolations are replaced.
+//! In this mode, `#` comments work as in Python, `//` is the floor division
+//! operator, and whitespace is kept exactly as written.
+//!
+//! ```
+//! # use inline_python::python;
+//! let x = 7;
+//! let half: i32 = python! {
+//!     #![verbatim]
+//!     ## This is a Python comment.
+//!     'x // 2
+//! };
+//! assert_eq!(half, 3);
+//! ```
+//!
+//! The code still needs to be accepted by the Rust tokenizer, which rejects
+//! unbalanced brackets, single quoted strings, and invalid Rust escape codes,
+//! even inside `#` comments.
+//!
+//! ## Python versions
+//!
+//! The Python code is compiled to bytecode at compile time, using the Python
+//! interpreter found by the macro. If the program runs with a different version
+//! of Python, the bytecode can't be used. In that case, the embedded source code
+//! of the block is compiled again at runtime instead.
+//! Use [`on_version_mismatch`] to get notified when that happens.
 
-use pyo3::{types::PyDict, Python};
+use crate::run::run_python_code;
+use pyo3::{
+	exceptions::{PyNameError, PyTypeError},
+	types::PyDict,
+	FromPyObject, PyObject, PyResult, Python,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::panic::Location;
+use std::path::PathBuf;
 
+mod borrow;
+mod buffer;
+mod collections;
 mod context;
+mod derive;
+mod error;
+mod flush;
+mod function;
+#[cfg(feature = "log")]
+mod logging;
+mod module;
+mod output;
+mod python_function;
 mod run;
+#[cfg(feature = "serde")]
+mod serialize;
+mod task;
+mod transform;
+mod version;
+mod warning;
 
+pub use self::borrow::{Borrowable, Borrowed, Protocol};
+pub use self::buffer::{BufferElement, SliceBuffer};
 pub use self::context::Context;
+#[doc(hidden)]
+pub use self::derive::ContextFields;
+pub use self::derive::{FromContext, IntoContext};
+pub use self::error::InlinePythonError;
+pub use self::flush::set_flush_output;
+pub use self::function::IntoPyFunction;
+#[cfg(feature = "log")]
+pub use self::logging::{forward_logging, forward_logging_with_gil, try_forward_logging, try_forward_logging_with_gil};
+pub use self::output::{
+	set_stderr, set_stderr_with_gil, set_stdin, set_stdin_with_gil, set_stdout, set_stdout_with_gil, try_set_stderr,
+	try_set_stderr_with_gil, try_set_stdin, try_set_stdin_with_gil, try_set_stdout, try_set_stdout_with_gil, CapturedOutput,
+};
+pub use self::python_function::{FunctionArgs, PythonFunction};
+#[doc(hidden)]
+pub use self::run::EmbeddedCode;
+#[cfg(feature = "serde")]
+pub use self::serialize::{from_python, to_python, Serde};
+pub use self::version::{on_version_mismatch, VersionMismatch};
+pub use self::warning::PyWarning;
 pub use pyo3;
 
 /// A block of Python code within your Rust code.
 ///
-/// This macro can be used in three different ways:
+/// This macro can be used in four different ways:
 ///
 ///  1. By itself as a statement.
 ///     In this case, the Python code is executed directly.
@@ -133,45 +362,293 @@ pub use pyo3;
 ///
 ///  3. By passing it as an argument to a function taking a `PythonBlock`, such
 ///     as [`Context::run`].
+///     A block that uses `await` outside of a function can only be passed to
+///     [`Context::run_async`].
+///
+///  4. By assigning it to a variable of a type that can be converted from
+///     Python, such as `i32`, `String` or [`Extract<T>`].
+///     In this case, the Python code is executed directly, and its final
+///     expression or `return` value is converted to that type.
 ///
 /// See [the crate's module level documentation](index.html) for examples.
 pub use inline_python_macros::python;
 
+/// Define a Rust function with a body written in Python.
+///
+/// The body is compiled at build time, just like a `python!{}` block.
+/// The arguments are converted to Python using [`pyo3::ToPyObject`], and are
+/// available under their own names. The value of a `return` statement, or of
+/// the final expression, is converted to the return type of the function.
+///
+/// ```
+/// # use inline_python::python_fn;
+/// #[python_fn]
+/// fn add(a: i64, b: i64) -> i64 {
+///     return a + b
+/// }
+///
+/// assert_eq!(add(1, 2), 3);
+/// ```
+///
+/// The body of the function needs to be valid Rust syntax.
+/// Python code that isn't can be wrapped in `python!{}`:
+///
+/// ```
+/// # use inline_python::python_fn;
+/// #[python_fn]
+/// fn count_words(text: &str) -> usize {
+///     python! {
+///         words = text.split()
+///         len(words)
+///     }
+/// }
+///
+/// assert_eq!(count_words("one two three"), 3);
+/// ```
+///
+/// Each call runs in a new [`Context`], and panics if the Python code fails.
+/// (This attribute can't be called `python`, since that name is already taken
+/// by the [`python!{}`](python) macro.)
+pub use inline_python_macros::python_fn;
+
+/// Derive [`FromContext`](trait@FromContext) for a struct with named fields.
+///
+/// Each field is extracted from the global variable with the same name,
+/// or the name given by a `#[context(name = "..")]` attribute.
+pub use inline_python_macros::FromContext;
+
+/// Derive [`IntoContext`](trait@IntoContext) for a struct with named fields.
+///
+/// Each field is stored in the global variable with the same name,
+/// or the name given by a `#[context(name = "..")]` attribute.
+pub use inline_python_macros::IntoContext;
+
+/// The variables of a `python!{}` block.
+///
+/// This is implemented by the closure generated by the `python!{}` macro,
+/// which sets the global variables for the interpolated Rust values, and
+/// returns a closure to write the `'mut` variables back after running the code.
+/// Setting a variable fails if its value can't be converted to Python.
 #[doc(hidden)]
-pub trait FromInlinePython<F: FnOnce(&PyDict)> {
-	fn from_python_macro(bytecode: &'static [u8], set_variables: F) -> Self;
+pub trait SetVariables {
+	type WriteBack: FnOnce(&PyDict) -> PyResult<()>;
+	fn set_variables(self, globals: &PyDict) -> PyResult<Self::WriteBack>;
+}
+
+impl<F, G> SetVariables for F
+where
+	F: FnOnce(&PyDict) -> PyResult<G>,
+	G: FnOnce(&PyDict) -> PyResult<()>,
+{
+	type WriteBack = G;
+	fn set_variables(self, globals: &PyDict) -> PyResult<G> {
+		self(globals)
+	}
+}
+
+/// Extract the final value of a `'mut` variable.
+///
+/// Errors mention the location of the `'mut` variable in the Rust source.
+#[doc(hidden)]
+#[track_caller]
+pub fn write_back<T: for<'p> FromPyObject<'p>>(globals: &PyDict, name: &str) -> PyResult<T> {
+	let location = Location::caller();
+	let rust_name = name.trim_start_matches("_RUST_");
+	let value = globals
+		.get_item(name)
+		.ok_or_else(|| PyNameError::new_err(format!("'mut {} at {} was deleted", rust_name, location)))?;
+	value.extract().map_err(|e| {
+		let py = globals.py();
+		let error = PyTypeError::new_err(format!(
+			"unable to convert 'mut {} at {} back to `{}`: {}",
+			rust_name,
+			location,
+			std::any::type_name::<T>(),
+			e.value(py),
+		));
+		error.set_cause(py, Some(e));
+		error
+	})
+}
+
+#[doc(hidden)]
+pub trait FromInlinePython<F: SetVariables> {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self;
 }
 
 /// Converting a `python!{}` block to `()` will run the Python code.
 ///
 /// This happens when `python!{}` is used as a statement by itself.
-impl<F: FnOnce(&PyDict)> FromInlinePython<F> for () {
-	fn from_python_macro(bytecode: &'static [u8], set_variables: F) {
-		let _: Context = FromInlinePython::from_python_macro(bytecode, set_variables);
+impl<F: SetVariables> FromInlinePython<F> for () {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) {
+		let _: Context = FromInlinePython::from_python_macro(embedded, set_variables);
 	}
 }
 
 /// Assigning a `python!{}` block to a `Context` will run the Python code and capture the resulting context.
-impl<F: FnOnce(&PyDict)> FromInlinePython<F> for Context {
-	fn from_python_macro(bytecode: &'static [u8], set_variables: F) -> Self {
+impl<F: SetVariables> FromInlinePython<F> for Context {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
 		Python::with_gil(|py| {
 			let context = Context::new_with_gil(py);
-			context.run_with_gil(py, PythonBlock { bytecode, set_variables });
+			context.run_with_gil(py, PythonBlock { embedded, set_variables });
 			context
 		})
 	}
 }
 
+/// Assigning a `python!{}` block to an `Extract<T>` will run the Python code and convert its result to `T`.
+impl<F: SetVariables, T: for<'p> FromPyObject<'p>> FromInlinePython<F> for Extract<T> {
+	#[track_caller]
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
+		Extract(run_and_extract(embedded, set_variables))
+	}
+}
+
+macro_rules! impl_from_inline_python {
+	(generic $(<$($param:ident),*> $type:ty,)*) => {
+		$(impl_from_inline_python!(@impl [$($param),*] $type);)*
+	};
+	($($type:ty,)*) => {
+		$(impl_from_inline_python!(@impl [] $type);)*
+	};
+	(@impl [$($param:ident),*] $type:ty) => {
+		/// Assigning a `python!{}` block to this type will run the Python code and convert its result.
+		impl<F: SetVariables, $($param),*> FromInlinePython<F> for $type
+		where
+			Self: for<'p> FromPyObject<'p>,
+		{
+			#[track_caller]
+			fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
+				run_and_extract(embedded, set_variables)
+			}
+		}
+	};
+}
+
+impl_from_inline_python! {
+	bool, char, String, PathBuf, OsString, PyObject,
+	i8, i16, i32, i64, i128, isize,
+	u8, u16, u32, u64, u128, usize,
+	f32, f64,
+}
+
+impl_from_inline_python! {
+	generic
+	<T> Option<T>,
+	<T> Vec<T>,
+	<T, S> HashSet<T, S>,
+	<T> BTreeSet<T>,
+	<K, V, S> HashMap<K, V, S>,
+	<K, V> BTreeMap<K, V>,
+	<A> (A,),
+	<A, B> (A, B),
+	<A, B, C> (A, B, C),
+	<A, B, C, D> (A, B, C, D),
+	<A, B, C, D, E> (A, B, C, D, E),
+	<A, B, C, D, E, G> (A, B, C, D, E, G),
+}
+
+#[cfg(feature = "serde")]
+impl_from_inline_python! {
+	generic
+	<T> Serde<T>,
+}
+
+/// Run a `python!{}` block in a new context and convert its result.
+///
+/// Panics with the location of the `python!{}` block if that fails.
+#[track_caller]
+fn run_and_extract<T: for<'p> FromPyObject<'p>, F: SetVariables>(embedded: &'static EmbeddedCode, set_variables: F) -> T {
+	let location = Location::caller();
+	Python::with_gil(|py| {
+		let context = Context::new_with_gil(py);
+		let result = set_variables.set_variables(context.globals(py)).and_then(|write_back| {
+			let result = run_python_code(py, &context, embedded)?;
+			write_back(context.globals(py))?;
+			Ok(result)
+		});
+		let result = match result {
+			Ok(result) => result,
+			Err(e) => {
+				e.print(py);
+				panic!("python!{{...}} at {} failed to execute", location);
+			}
+		};
+		match result.extract() {
+			Ok(value) => value,
+			Err(e) => {
+				e.print(py);
+				panic!(
+					"Unable to convert the result of python!{{...}} at {} to `{}`",
+					location,
+					std::any::type_name::<T>()
+				);
+			}
+		}
+	})
+}
+
+/// The result of a `python!{}` block, converted to `T`.
+///
+/// Assigning a `python!{}` block to an `Extract<T>` works for any `T` that
+/// implements [`pyo3::FromPyObject`], including `#[pyclass]` types and
+/// types with a custom `FromPyObject` implementation:
+///
+/// ```
+/// # use inline_python::{python, Extract};
+/// let Extract(n): Extract<i32> = python! {
+///   1 + 2
+/// };
+///
+/// assert_eq!(n, 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Extract<T>(pub T);
+
 /// Using a `python!{}` block as a `PythonBlock` object will not do anything yet.
-impl<F: FnOnce(&PyDict)> FromInlinePython<F> for PythonBlock<F> {
-	fn from_python_macro(bytecode: &'static [u8], set_variables: F) -> Self {
-		Self { bytecode, set_variables }
+impl<F: SetVariables> FromInlinePython<F> for PythonBlock<F> {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
+		Self { embedded, set_variables }
 	}
 }
 
 /// Represents a `python!{}` block.
 #[doc(hidden)]
 pub struct PythonBlock<F> {
-	bytecode: &'static [u8],
+	embedded: &'static EmbeddedCode,
+	set_variables: F,
+}
+
+/// Converting a `python!{}` block that uses `await` outside of a function.
+///
+/// This is only implemented for [`AsyncPythonBlock`], so such a block is a
+/// compile error anywhere but as the argument of [`Context::run_async`].
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+	message = "python!{{...}} uses `await` outside of a function, so it must be run with `Context::run_async`",
+	label = "this block uses `await`"
+)]
+pub trait FromAsyncInlinePython<F: SetVariables> {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self;
+}
+
+/// Using a `python!{}` block as an `AsyncPythonBlock` object will not do anything yet.
+impl<F: SetVariables> FromInlinePython<F> for AsyncPythonBlock<F> {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
+		Self { embedded, set_variables }
+	}
+}
+
+/// Using a `python!{}` block with `await` as an `AsyncPythonBlock` object will not do anything yet.
+impl<F: SetVariables> FromAsyncInlinePython<F> for AsyncPythonBlock<F> {
+	fn from_python_macro(embedded: &'static EmbeddedCode, set_variables: F) -> Self {
+		Self { embedded, set_variables }
+	}
+}
+
+/// Represents a `python!{}` block that might use `await` outside of a function.
+#[doc(hidden)]
+pub struct AsyncPythonBlock<F> {
+	embedded: &'static EmbeddedCode,
 	set_variables: F,
 }
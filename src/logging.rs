@@ -0,0 +1,199 @@
+//! Forwarding records of Python's `logging` module to the [`log`] crate.
+
+use crate::InlinePythonError;
+use pyo3::{
+	sync::GILOnceCell,
+	types::{PyCFunction, PyDict, PyModule, PyTuple},
+	Py, PyAny, PyResult, Python,
+};
+
+/// The Python handler that forwards records to Rust.
+///
+/// The handler is not added to any logger, such that `logging.basicConfig()` and
+/// other configuration of Python's loggers work as usual. Instead, `Logger.callHandlers`
+/// is wrapped, the first time it is needed, to also pass records that reach the root logger
+/// to the handler when forwarding is enabled for all Python code, or for the context that
+/// is running code, which is stored in a context variable.
+/// A forwarded record is not also passed to `logging.lastResort`.
+const HANDLER: &str = r#"
+import contextvars
+import logging
+
+_forward = contextvars.ContextVar("inline_python_logging", default=False)
+_forward_all = False
+_handler = None
+_call_handlers = logging.Logger.callHandlers
+
+def _reaches_root(logger):
+	while logger.propagate and logger.parent is not None:
+		logger = logger.parent
+	return logger.parent is None
+
+def _forwarding_call_handlers(self, record):
+	if (_forward_all or _forward.get()) and _reaches_root(self):
+		_handler.handle(record)
+		if not self.hasHandlers():
+			return
+	_call_handlers(self, record)
+
+class RustHandler(logging.Handler):
+	def __init__(self, enabled, log):
+		super().__init__()
+		self._enabled = enabled
+		self._log = log
+
+	def emit(self, record):
+		try:
+			if self._enabled(record.levelno, record.name):
+				self._log(record.levelno, record.name, self.format(record), record.pathname, record.lineno)
+		except Exception:
+			self.handleError(record)
+
+def install(enabled, log):
+	global _handler
+	_handler = RustHandler(enabled, log)
+	logging.Logger.callHandlers = _forwarding_call_handlers
+
+def forward_all():
+	global _forward_all
+	_forward_all = True
+
+def enter():
+	return _forward.set(True)
+
+def exit(token):
+	_forward.reset(token)
+"#;
+
+/// Forward records of Python's `logging` module to the [`log`] crate, for all Python code.
+///
+/// Every record that reaches Python's root logger is forwarded
+/// to the Rust logger with the equivalent level, the name of the Python logger as target,
+/// the formatted message, and the file and line of the call.
+/// For `python!{}` blocks, the file and line are those in the Rust source file.
+///
+/// Records are only formatted and forwarded if the Rust logger is enabled
+/// for their level and target, which is checked when they are logged.
+/// The levels of the Python loggers still apply before that, and are not changed.
+/// The handlers of the Python loggers are not changed either, such that
+/// `logging.basicConfig()` still works, and its handlers receive the records as well.
+/// Since the level of Python's root logger is `WARNING` by default,
+/// use for example `logging.getLogger().setLevel(logging.DEBUG)` in Python to
+/// forward records of lower levels as well.
+///
+/// To use this with a `tracing` subscriber, forward `log` records to `tracing`
+/// using the `tracing-log` crate, which `tracing-subscriber` does by default.
+///
+/// ```
+/// # use inline_python::python;
+/// inline_python::forward_logging();
+/// python! {
+///     import logging
+///     logging.getLogger("plugin").warning("forwarded to the Rust logger")
+/// }
+/// ```
+///
+/// Calling it again has no effect.
+///
+/// This function panics if the handler can't be installed.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`forward_logging_with_gil`] instead.
+pub fn forward_logging() {
+	Python::with_gil(forward_logging_with_gil);
+}
+
+/// Forward records of Python's `logging` module to the [`log`] crate, for all Python code.
+///
+/// This function panics if the handler can't be installed.
+pub fn forward_logging_with_gil(py: Python<'_>) {
+	if let Err(e) = try_forward_logging_with_gil(py) {
+		e.print(py);
+		panic!("Unable to forward Python logging");
+	}
+}
+
+/// Forward records of Python's `logging` module to the [`log`] crate, for all Python code.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`try_forward_logging_with_gil`] instead.
+pub fn try_forward_logging() -> Result<(), InlinePythonError> {
+	Python::with_gil(try_forward_logging_with_gil)
+}
+
+/// Forward records of Python's `logging` module to the [`log`] crate, for all Python code.
+pub fn try_forward_logging_with_gil(py: Python<'_>) -> Result<(), InlinePythonError> {
+	handler_module(py)
+		.and_then(|module| module.getattr("forward_all")?.call0())
+		.map(drop)
+		.map_err(|e| InlinePythonError::new(py, e))
+}
+
+/// Get the Python module of the handler, installing the handler the first time.
+pub(crate) fn handler_module(py: Python<'_>) -> PyResult<&PyModule> {
+	static HANDLER_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+	HANDLER_MODULE
+		.get_or_try_init(py, || -> PyResult<_> {
+			let module = PyModule::from_code(py, HANDLER, "<inline-python>", "_inline_python_logging")?;
+			let enabled = PyCFunction::new_closure(py, None, None, |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<bool> {
+				let (level, target): (u32, &str) = args.extract()?;
+				let level = rust_level(level);
+				Ok(level <= log::max_level() && log::logger().enabled(&log::Metadata::builder().level(level).target(target).build()))
+			})?;
+			let log = PyCFunction::new_closure(py, None, None, |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<()> {
+				let (level, target, message, file, line): (u32, &str, &str, &str, u32) = args.extract()?;
+				log::logger().log(
+					&log::Record::builder()
+						.level(rust_level(level))
+						.target(target)
+						.args(format_args!("{}", message))
+						.file(Some(file))
+						.line(Some(line))
+						.build(),
+				);
+				Ok(())
+			})?;
+			module.getattr("install")?.call1((enabled, log))?;
+			Ok(module.into())
+		})
+		.map(|module| module.as_ref(py))
+}
+
+/// Forwards records for the current thread or `asyncio` task, until dropped.
+pub(crate) struct ForwardedLogging<'p> {
+	/// The module and the token to restore the previous setting.
+	entered: Option<(&'p PyModule, &'p PyAny)>,
+}
+
+impl<'p> ForwardedLogging<'p> {
+	pub fn enter(py: Python<'p>, forward: bool) -> PyResult<Self> {
+		if !forward {
+			return Ok(Self { entered: None });
+		}
+		let module = handler_module(py)?;
+		let token = module.getattr("enter")?.call0()?;
+		Ok(Self {
+			entered: Some((module, token)),
+		})
+	}
+}
+
+impl Drop for ForwardedLogging<'_> {
+	fn drop(&mut self) {
+		if let Some((module, token)) = self.entered {
+			if let Err(e) = module.getattr("exit").and_then(|exit| exit.call1((token,))) {
+				e.print(module.py());
+			}
+		}
+	}
+}
+
+fn rust_level(level: u32) -> log::Level {
+	match level {
+		40.. => log::Level::Error,
+		30..=39 => log::Level::Warn,
+		20..=29 => log::Level::Info,
+		10..=19 => log::Level::Debug,
+		_ => log::Level::Trace,
+	}
+}
@@ -0,0 +1,41 @@
+use pyo3::{
+	types::{PyDict, PyList, PyModule},
+	PyResult, Python,
+};
+
+/// Register a module in `sys.modules` under the given dotted name, so it can be imported.
+///
+/// Parent packages that don't exist yet are created as empty packages,
+/// and the module is added as an attribute of its parent.
+/// Submodules of the module are registered under their name within the module.
+pub(crate) fn register_module(py: Python, name: &str, module: &PyModule) -> PyResult<()> {
+	let modules: &PyDict = py.import("sys")?.getattr("modules")?.downcast()?;
+
+	if let Some((parent, child)) = name.rsplit_once('.') {
+		let parent_module = match modules.get_item(parent) {
+			Some(parent_module) => parent_module,
+			None => {
+				let package = PyModule::new(py, parent)?;
+				package.setattr("__path__", PyList::empty(py))?;
+				register_module(py, parent, package)?;
+				package
+			}
+		};
+		parent_module.setattr(child, module)?;
+	}
+
+	module.setattr("__name__", name)?;
+	modules.set_item(name, module)?;
+
+	for (key, value) in module.dict() {
+		if let Ok(submodule) = value.downcast::<PyModule>() {
+			// Skip modules that are imported normally, such as `sys`, and the module itself.
+			let already_registered = modules.get_item(submodule.name()?).is_some_and(|m| m.is(submodule));
+			if !already_registered {
+				register_module(py, &format!("{}.{}", name, key), submodule)?;
+			}
+		}
+	}
+
+	Ok(())
+}
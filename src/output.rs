@@ -0,0 +1,328 @@
+//! Routing Python's `sys.stdin`, `sys.stdout` and `sys.stderr` to Rust.
+
+use crate::InlinePythonError;
+use pyo3::{
+	sync::GILOnceCell,
+	types::{PyBytes, PyCFunction, PyDict, PyModule, PyTuple},
+	Py, PyAny, PyObject, PyResult, Python,
+};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The output of Python code, captured by [`Context::run_capturing`](crate::Context::run_capturing).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+	/// Everything written to `sys.stdout`.
+	pub stdout: String,
+	/// Everything written to `sys.stderr`.
+	pub stderr: String,
+}
+
+/// Route Python's `sys.stdout` to a Rust writer, for all Python code.
+///
+/// After this, everything written to `sys.stdout` goes to `writer`.
+/// A stream set with [`Context::set_stdout`](crate::Context::set_stdout)
+/// takes precedence for code that runs in that context.
+///
+/// ```
+/// # use inline_python::python;
+/// let (tx, rx) = std::sync::mpsc::channel();
+///
+/// struct Lines(std::sync::mpsc::Sender<String>);
+///
+/// impl std::io::Write for Lines {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         self.0.send(String::from_utf8_lossy(buf).into_owned()).unwrap();
+///         Ok(buf.len())
+///     }
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// inline_python::set_stdout(Lines(tx));
+/// python! {
+///     print("hello", end="")
+/// }
+/// assert_eq!(rx.recv().unwrap(), "hello");
+/// ```
+///
+/// This function panics if the Python object can't be created.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`set_stdout_with_gil`] instead.
+pub fn set_stdout(writer: impl Write + Send + 'static) {
+	Python::with_gil(|py| set_stdout_with_gil(py, writer));
+}
+
+/// Route Python's `sys.stdout` to a Rust writer, for all Python code.
+///
+/// This function panics if the Python object can't be created.
+pub fn set_stdout_with_gil(py: Python<'_>, writer: impl Write + Send + 'static) {
+	if let Err(e) = try_set_stdout_with_gil(py, writer) {
+		e.print(py);
+		panic!("Unable to set sys.stdout");
+	}
+}
+
+/// Route Python's `sys.stdout` to a Rust writer, for all Python code.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`try_set_stdout_with_gil`] instead.
+pub fn try_set_stdout(writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+	Python::with_gil(|py| try_set_stdout_with_gil(py, writer))
+}
+
+/// Route Python's `sys.stdout` to a Rust writer, for all Python code.
+pub fn try_set_stdout_with_gil(py: Python<'_>, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+	set_default_stream(py, "stdout", new_writer(py, writer)).map_err(|e| InlinePythonError::new(py, e))
+}
+
+/// Route Python's `sys.stderr` to a Rust writer, for all Python code.
+///
+/// See [`set_stdout`].
+///
+/// This function panics if the Python object can't be created.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`set_stderr_with_gil`] instead.
+pub fn set_stderr(writer: impl Write + Send + 'static) {
+	Python::with_gil(|py| set_stderr_with_gil(py, writer));
+}
+
+/// Route Python's `sys.stderr` to a Rust writer, for all Python code.
+///
+/// This function panics if the Python object can't be created.
+pub fn set_stderr_with_gil(py: Python<'_>, writer: impl Write + Send + 'static) {
+	if let Err(e) = try_set_stderr_with_gil(py, writer) {
+		e.print(py);
+		panic!("Unable to set sys.stderr");
+	}
+}
+
+/// Route Python's `sys.stderr` to a Rust writer, for all Python code.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`try_set_stderr_with_gil`] instead.
+pub fn try_set_stderr(writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+	Python::with_gil(|py| try_set_stderr_with_gil(py, writer))
+}
+
+/// Route Python's `sys.stderr` to a Rust writer, for all Python code.
+pub fn try_set_stderr_with_gil(py: Python<'_>, writer: impl Write + Send + 'static) -> Result<(), InlinePythonError> {
+	set_default_stream(py, "stderr", new_writer(py, writer)).map_err(|e| InlinePythonError::new(py, e))
+}
+
+/// Feed Python's `sys.stdin` from a Rust reader, for all Python code.
+///
+/// After this, `sys.stdin` is a Python text stream that reads from `reader`,
+/// which is used by `input()`.
+/// A stream set with [`Context::set_stdin`](crate::Context::set_stdin)
+/// takes precedence for code that runs in that context.
+///
+/// This function panics if the Python object can't be created.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`set_stdin_with_gil`] instead.
+pub fn set_stdin(reader: impl Read + Send + 'static) {
+	Python::with_gil(|py| set_stdin_with_gil(py, reader));
+}
+
+/// Feed Python's `sys.stdin` from a Rust reader, for all Python code.
+///
+/// This function panics if the Python object can't be created.
+pub fn set_stdin_with_gil(py: Python<'_>, reader: impl Read + Send + 'static) {
+	if let Err(e) = try_set_stdin_with_gil(py, reader) {
+		e.print(py);
+		panic!("Unable to set sys.stdin");
+	}
+}
+
+/// Feed Python's `sys.stdin` from a Rust reader, for all Python code.
+///
+/// This function temporarily acquires the GIL.
+/// If you already have the GIL, you can use [`try_set_stdin_with_gil`] instead.
+pub fn try_set_stdin(reader: impl Read + Send + 'static) -> Result<(), InlinePythonError> {
+	Python::with_gil(|py| try_set_stdin_with_gil(py, reader))
+}
+
+/// Feed Python's `sys.stdin` from a Rust reader, for all Python code.
+pub fn try_set_stdin_with_gil(py: Python<'_>, reader: impl Read + Send + 'static) -> Result<(), InlinePythonError> {
+	set_default_stream(py, "stdin", new_reader(py, reader)).map_err(|e| InlinePythonError::new(py, e))
+}
+
+/// Set the default `sys` stream named `name`, which is used outside of contexts with their own stream.
+fn set_default_stream(py: Python<'_>, name: &str, stream: PyResult<PyObject>) -> PyResult<()> {
+	streams_module(py)?.as_ref(py).getattr("set_default")?.call1((name, stream?))?;
+	Ok(())
+}
+
+/// The Python classes of the stream objects that write to and read from Rust,
+/// and of the streams in the `sys` module that route to the streams of the running context.
+///
+/// The streams of a context are stored in a context variable while it runs code,
+/// which is separate for every thread and every `asyncio` task.
+const STREAMS: &str = r#"
+import contextvars
+import io
+import sys
+
+_streams = contextvars.ContextVar("inline_python_streams", default=None)
+
+class Stream:
+	def __init__(self, name, default):
+		self._name = name
+		self._default = default
+
+	def _target(self):
+		streams = _streams.get()
+		if streams is not None:
+			stream = streams.get(self._name)
+			if stream is not None:
+				return stream
+		return self._default
+
+	def __getattr__(self, name):
+		return getattr(self._target(), name)
+
+	def __iter__(self):
+		return iter(self._target())
+
+	def __repr__(self):
+		return repr(self._target())
+
+def install(name):
+	if not isinstance(getattr(sys, name, None), Stream):
+		setattr(sys, name, Stream(name, getattr(sys, name, None)))
+
+def set_default(name, stream):
+	current = getattr(sys, name, None)
+	if isinstance(current, Stream):
+		current._default = stream
+	else:
+		setattr(sys, name, stream)
+
+def enter(streams):
+	for name in streams:
+		install(name)
+	return _streams.set(streams)
+
+def exit(token):
+	streams = _streams.get()
+	_streams.reset(token)
+	for stream in streams.values():
+		stream.flush()
+
+class RustReader(io.RawIOBase):
+	def __init__(self, read):
+		self._read = read
+
+	def readable(self):
+		return True
+
+	def readinto(self, buffer):
+		data = self._read(len(buffer))
+		buffer[:len(data)] = data
+		return len(data)
+
+def rust_reader(read):
+	return io.TextIOWrapper(io.BufferedReader(RustReader(read)), encoding="utf-8")
+
+class RustWriter(io.TextIOBase):
+	def __init__(self, write, flush):
+		self._write = write
+		self._flush = flush
+
+	@property
+	def encoding(self):
+		return "utf-8"
+
+	def writable(self):
+		return True
+
+	def write(self, s):
+		if not isinstance(s, str):
+			raise TypeError(f"write() argument must be str, not {type(s).__name__}")
+		self._write(s)
+		return len(s)
+
+	def flush(self):
+		self._flush()
+"#;
+
+/// Create a Python text stream that writes to `writer`.
+pub(crate) fn new_writer(py: Python<'_>, writer: impl Write + Send + 'static) -> PyResult<PyObject> {
+	let module = streams_module(py)?;
+	let writer = Arc::new(Mutex::new(writer));
+	let writer2 = writer.clone();
+	let write = PyCFunction::new_closure(py, None, None, move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<()> {
+		let (text,): (&str,) = args.extract()?;
+		writer.lock().unwrap_or_else(PoisonError::into_inner).write_all(text.as_bytes())?;
+		Ok(())
+	})?;
+	let flush = PyCFunction::new_closure(py, None, None, move |_args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<()> {
+		writer2.lock().unwrap_or_else(PoisonError::into_inner).flush()?;
+		Ok(())
+	})?;
+	Ok(module.as_ref(py).getattr("RustWriter")?.call1((write, flush))?.into())
+}
+
+/// Create a Python text stream that reads from `reader`.
+///
+/// The GIL is released while reading, since reading might block.
+pub(crate) fn new_reader(py: Python<'_>, reader: impl Read + Send + 'static) -> PyResult<PyObject> {
+	let module = streams_module(py)?;
+	let reader = Arc::new(Mutex::new(reader));
+	let read = PyCFunction::new_closure(
+		py,
+		None,
+		None,
+		move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<Py<PyBytes>> {
+			let py = args.py();
+			let (size,): (usize,) = args.extract()?;
+			let mut buffer = vec![0; size];
+			let n = py.allow_threads(|| reader.lock().unwrap_or_else(PoisonError::into_inner).read(&mut buffer))?;
+			Ok(PyBytes::new(py, &buffer[..n]).into())
+		},
+	)?;
+	Ok(module.as_ref(py).getattr("rust_reader")?.call1((read,))?.into())
+}
+
+fn streams_module(py: Python<'_>) -> PyResult<&Py<PyModule>> {
+	static STREAMS_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+	STREAMS_MODULE.get_or_try_init(py, || -> PyResult<_> {
+		Ok(PyModule::from_code(py, STREAMS, "<inline-python>", "_inline_python_streams")?.into())
+	})
+}
+
+/// Routes the streams in the `sys` module to the streams of a context, until dropped.
+///
+/// This only affects the current thread or `asyncio` task.
+pub(crate) struct ContextStreams<'p> {
+	/// The module and the token to restore the previous streams.
+	entered: Option<(&'p PyModule, &'p PyAny)>,
+}
+
+impl<'p> ContextStreams<'p> {
+	/// Route the `sys` streams named by the keys of `streams` to their values.
+	pub fn enter(py: Python<'p>, streams: &'p PyDict) -> PyResult<Self> {
+		if streams.is_empty() {
+			return Ok(Self { entered: None });
+		}
+		let module = streams_module(py)?.as_ref(py);
+		let token = module.getattr("enter")?.call1((streams,))?;
+		Ok(Self {
+			entered: Some((module, token)),
+		})
+	}
+}
+
+impl Drop for ContextStreams<'_> {
+	fn drop(&mut self) {
+		if let Some((module, token)) = self.entered {
+			if let Err(e) = module.getattr("exit").and_then(|exit| exit.call1((token,))) {
+				e.print(module.py());
+			}
+		}
+	}
+}
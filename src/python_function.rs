@@ -0,0 +1,171 @@
+use crate::InlinePythonError;
+use pyo3::{
+	exceptions::PyTypeError,
+	types::{IntoPyDict, PyDict, PyTuple},
+	FromPyObject, IntoPy, Py, PyAny, PyObject, PyResult, Python,
+};
+use std::marker::PhantomData;
+
+/// The positional arguments of a [`PythonFunction`].
+///
+/// This is implemented for tuples of up to eight elements that can be converted to Python objects.
+pub trait FunctionArgs: IntoPy<Py<PyTuple>> {
+	/// The number of positional arguments.
+	const LEN: usize;
+}
+
+macro_rules! impl_function_args {
+	($n:literal $(, $arg:ident)*) => {
+		impl<$($arg: IntoPy<PyObject>,)*> FunctionArgs for ($($arg,)*) {
+			const LEN: usize = $n;
+		}
+	};
+}
+
+impl_function_args!(0);
+impl_function_args!(1, A);
+impl_function_args!(2, A, B);
+impl_function_args!(3, A, B, C);
+impl_function_args!(4, A, B, C, D);
+impl_function_args!(5, A, B, C, D, E);
+impl_function_args!(6, A, B, C, D, E, F);
+impl_function_args!(7, A, B, C, D, E, F, G);
+impl_function_args!(8, A, B, C, D, E, F, G, H);
+
+/// A handle to a Python function, with typed arguments and return value.
+///
+/// This is created by [`Context::function`](crate::Context::function).
+/// The handle stays valid after the context is changed or dropped,
+/// and can be called any number of times.
+/// Use [`PyObject`] as the return type to keep the result untyped.
+///
+/// ```
+/// # use inline_python::{Context, python};
+/// let c = Context::new();
+///
+/// c.run(python! {
+///     def score(row, weight=1.0):
+///         return sum(row) * weight
+/// });
+///
+/// let score = c.function::<(Vec<f64>,), f64>("score").unwrap();
+///
+/// assert_eq!(score.call((vec![1.0, 2.0],)).unwrap(), 3.0);
+/// assert_eq!(score.call_with_kwargs((vec![1.0, 2.0],), [("weight", 2.0)]).unwrap(), 6.0);
+/// ```
+pub struct PythonFunction<Args, R> {
+	name: String,
+	function: PyObject,
+	types: PhantomData<fn(Args) -> R>,
+}
+
+impl<Args: FunctionArgs, R: for<'p> FromPyObject<'p>> PythonFunction<Args, R> {
+	/// Check that `function` is callable with `Args::LEN` positional arguments.
+	pub(crate) fn new(py: Python, name: &str, function: &PyAny) -> PyResult<Self> {
+		if !function.is_callable() {
+			return Err(PyTypeError::new_err(format!("'{}' is not callable", name)));
+		}
+		// Functions without a signature, such as some built-in functions, are not checked.
+		if let Ok(signature) = py.import("inspect")?.call_method1("signature", (function,)) {
+			let args = PyTuple::new(py, (0..Args::LEN).map(|_| py.None()));
+			// Binding too few arguments fails on the first missing one, but keyword-only
+			// parameters without a default might be given as keyword arguments, so only
+			// bind all arguments if positional arguments are missing.
+			let bind = if required_positional(signature)? > Args::LEN {
+				"bind"
+			} else {
+				"bind_partial"
+			};
+			if let Err(e) = signature.call_method1(bind, args) {
+				let error = PyTypeError::new_err(format!(
+					"'{}' can not be called with {} positional argument{}: {}",
+					name,
+					Args::LEN,
+					if Args::LEN == 1 { "" } else { "s" },
+					e.value(py),
+				));
+				error.set_cause(py, Some(e));
+				return Err(error);
+			}
+		}
+		Ok(Self {
+			name: name.to_string(),
+			function: function.into(),
+			types: PhantomData,
+		})
+	}
+
+	/// The name the function was looked up by.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Call the function with positional arguments.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`PythonFunction::call_with_gil`] instead.
+	pub fn call(&self, args: Args) -> Result<R, InlinePythonError> {
+		Python::with_gil(|py| self.call_with_gil(py, args))
+	}
+
+	/// Call the function with positional arguments.
+	pub fn call_with_gil(&self, py: Python<'_>, args: Args) -> Result<R, InlinePythonError> {
+		self.call_impl(py, args, None)
+	}
+
+	/// Call the function with positional and keyword arguments.
+	///
+	/// This function temporarily acquires the GIL.
+	/// If you already have the GIL, you can use [`PythonFunction::call_with_kwargs_with_gil`] instead.
+	pub fn call_with_kwargs(&self, args: Args, kwargs: impl IntoPyDict) -> Result<R, InlinePythonError> {
+		Python::with_gil(|py| self.call_with_kwargs_with_gil(py, args, kwargs))
+	}
+
+	/// Call the function with positional and keyword arguments.
+	pub fn call_with_kwargs_with_gil(&self, py: Python<'_>, args: Args, kwargs: impl IntoPyDict) -> Result<R, InlinePythonError> {
+		self.call_impl(py, args, Some(kwargs.into_py_dict(py)))
+	}
+
+	fn call_impl(&self, py: Python<'_>, args: Args, kwargs: Option<&PyDict>) -> Result<R, InlinePythonError> {
+		let result = self
+			.function
+			.as_ref(py)
+			.call(args.into_py(py).as_ref(py), kwargs)
+			.and_then(|result| {
+				result.extract().map_err(|e| {
+					let error = PyTypeError::new_err(format!(
+						"unable to convert the result of '{}' to `{}`: {}",
+						self.name,
+						std::any::type_name::<R>(),
+						e.value(py),
+					));
+					error.set_cause(py, Some(e));
+					error
+				})
+			});
+		result.map_err(|e| InlinePythonError::new(py, e))
+	}
+}
+
+impl<Args, R> Clone for PythonFunction<Args, R> {
+	fn clone(&self) -> Self {
+		Python::with_gil(|py| Self {
+			name: self.name.clone(),
+			function: self.function.clone_ref(py),
+			types: PhantomData,
+		})
+	}
+}
+
+/// The number of positional parameters without a default value in an `inspect.Signature`.
+fn required_positional(signature: &PyAny) -> PyResult<usize> {
+	let mut required = 0;
+	for parameter in signature.getattr("parameters")?.call_method0("values")?.iter()? {
+		let parameter = parameter?;
+		let kind: &str = parameter.getattr("kind")?.getattr("name")?.extract()?;
+		if matches!(kind, "POSITIONAL_ONLY" | "POSITIONAL_OR_KEYWORD") && parameter.getattr("default")?.is(parameter.getattr("empty")?) {
+			required += 1;
+		}
+	}
+	Ok(required)
+}
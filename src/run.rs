@@ -1,11 +1,198 @@
+use crate::flush::{flush_after, flush_before};
+use crate::output::ContextStreams;
+use crate::task::PythonTask;
+use crate::version::{magic_number, report_version_mismatch};
+use crate::warning::ContextWarnings;
 use crate::Context;
-use pyo3::{ffi, types::PyAny, AsPyPointer, PyObject, PyResult, Python};
+use pyo3::{
+	exceptions::PyRuntimeError,
+	ffi,
+	sync::GILProtected,
+	types::{PyAny, PyDict},
+	AsPyPointer, Py, PyObject, PyResult, Python,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-pub fn run_python_code<'p>(py: Python<'p>, context: &Context, bytecode: &[u8]) -> PyResult<&'p PyAny> {
+/// The code objects of all `python!{}` blocks that have run, keyed by the address of their bytecode.
+static CODE_OBJECTS: GILProtected<RefCell<BTreeMap<usize, PyObject>>> = GILProtected::new(RefCell::new(BTreeMap::new()));
+
+/// The code objects of all Python files that have run, with the modification time of the file they were compiled from.
+static FILE_CODE_OBJECTS: GILProtected<RefCell<BTreeMap<PathBuf, (SystemTime, PyObject)>>> =
+	GILProtected::new(RefCell::new(BTreeMap::new()));
+
+/// The code of a `python!{}` block, as embedded by the macro.
+///
+/// Next to the bytecode, this contains the source code, so the code can be
+/// compiled again if the bytecode was made for a different version of Python.
+#[doc(hidden)]
+pub struct EmbeddedCode {
+	/// The marshalled code object.
+	pub bytecode: &'static [u8],
+	/// The magic number (`importlib.util.MAGIC_NUMBER`) of the Python version that generated the bytecode.
+	pub magic: &'static [u8],
+	/// The Python version that generated the bytecode.
+	pub python_version: &'static str,
+	/// The Python source code.
+	pub source: &'static str,
+	/// The Rust source file containing the `python!{}` block.
+	pub filename: &'static str,
+	/// The line in the Rust source file that the Python source code starts at.
+	pub line: usize,
+	/// The parameters of a `#[python_fn]` function, whose body is this code.
+	pub parameters: Option<&'static [&'static str]>,
+}
+
+/// Get the code object for the embedded code.
+///
+/// The code object is only created the first time a block runs, and reused afterwards.
+fn cached_code_object(py: Python, embedded: &EmbeddedCode) -> PyResult<PyObject> {
+	let key = embedded.bytecode.as_ptr() as usize;
+	if let Some(code) = CODE_OBJECTS.get(py).borrow().get(&key) {
+		return Ok(code.clone_ref(py));
+	}
+	let code = code_object(py, embedded)?;
+	CODE_OBJECTS.get(py).borrow_mut().insert(key, code.clone_ref(py));
+	Ok(code)
+}
+
+/// Create the code object for the embedded code.
+///
+/// If the bytecode was generated by a different version of Python, this
+/// compiles the source code instead.
+fn code_object(py: Python, embedded: &EmbeddedCode) -> PyResult<PyObject> {
+	if embedded.magic == magic_number(py)? {
+		unsafe {
+			let bytecode = embedded.bytecode;
+			let ptr = ffi::PyMarshal_ReadObjectFromString(bytecode.as_ptr() as *const _, bytecode.len() as isize);
+			PyObject::from_owned_ptr_or_err(py, ptr)
+		}
+	} else {
+		report_version_mismatch(py, embedded);
+		let source = "\n".repeat(embedded.line.saturating_sub(1)) + embedded.source;
+		crate::transform::compile_block(py, &source, embedded.filename, embedded.parameters)
+	}
+}
+
+/// Compile Python source code as a script, with plain Python semantics.
+pub fn compile_source(py: Python, source: &str, filename: &str) -> PyResult<PyObject> {
+	crate::transform::compile_script(py, source, filename)
+}
+
+/// Compile a Python file as a script, with plain Python semantics.
+///
+/// The code object is reused until the modification time of the file changes.
+pub fn compile_file(py: Python, path: &Path) -> PyResult<PyObject> {
+	let modified = std::fs::metadata(path)?.modified()?;
+	if let Some((time, code)) = FILE_CODE_OBJECTS.get(py).borrow().get(path) {
+		if *time == modified {
+			return Ok(code.clone_ref(py));
+		}
+	}
+	let source = std::fs::read_to_string(path)?;
+	let code = compile_source(py, &source, &path.to_string_lossy())?;
+	FILE_CODE_OBJECTS
+		.get(py)
+		.borrow_mut()
+		.insert(path.to_path_buf(), (modified, code.clone_ref(py)));
+	Ok(code)
+}
+
+/// Run the code of a `python!{}` block and return its result.
+pub fn run_python_code<'p>(py: Python<'p>, context: &'p Context, embedded: &EmbeddedCode) -> PyResult<&'p PyAny> {
+	let code = cached_code_object(py, embedded)?;
+	run_code_object(py, context, &code)
+}
+
+/// Run the code and return the result of the block.
+///
+/// The result is the value of `_RUST_return` (see the `transform` module),
+/// which is removed from the globals again. If it is not set, the result is `None`.
+///
+/// Code that uses `await` outside of a function can only be run by [`start_python_code`].
+pub fn run_code_object<'p>(py: Python<'p>, context: &'p Context, code: &PyObject) -> PyResult<&'p PyAny> {
+	if is_coroutine(py, code)? {
+		return Err(PyRuntimeError::new_err(
+			"python!{...} uses `await` outside of a function, so it must be run with `Context::run_async`",
+		));
+	}
+	Settings::of(py, context).apply(py, || eval_code(py, context, code))?;
+	take_result(py, context)
+}
+
+/// Start running the code of a `python!{}` block that might use `await` outside of a function.
+///
+/// If it does, this returns the task that runs it, which takes the result of the block out of the globals.
+/// Otherwise, the code has already run, its result is removed from the globals, and this returns `None`.
+pub fn start_python_code(py: Python, context: &Context, embedded: &EmbeddedCode) -> PyResult<Option<PythonTask>> {
+	let code = cached_code_object(py, embedded)?;
+	let settings = Settings::of(py, context);
+	if !is_coroutine(py, &code)? {
+		settings.apply(py, || eval_code(py, context, &code))?;
+		take_result(py, context)?;
+		return Ok(None);
+	}
+	let coroutine = eval_code(py, context, &code)?;
+	PythonTask::start(py, coroutine, settings, context.globals.as_ref(py)).map(Some)
+}
+
+/// Whether the code uses `await` outside of a function, such that evaluating it only creates a coroutine.
+fn is_coroutine(py: Python, code: &PyObject) -> PyResult<bool> {
+	let flags: c_int = code.getattr(py, "co_flags")?.extract(py)?;
+	Ok(flags & ffi::CO_COROUTINE != 0)
+}
+
+fn eval_code<'p>(py: Python<'p>, context: &Context, code: &PyObject) -> PyResult<&'p PyAny> {
 	unsafe {
-		let ptr = ffi::PyMarshal_ReadObjectFromString(bytecode.as_ptr() as *const _, bytecode.len() as isize);
-		let code = PyObject::from_owned_ptr_or_err(py, ptr)?;
 		let result = ffi::PyEval_EvalCode(code.as_ptr(), context.globals.as_ptr(), std::ptr::null_mut());
 		py.from_owned_ptr_or_err(result)
 	}
 }
+
+/// Take the result of the block that ran last out of the globals.
+fn take_result<'p>(py: Python<'p>, context: &'p Context) -> PyResult<&'p PyAny> {
+	let globals = context.globals(py);
+	match globals.get_item("_RUST_return") {
+		Some(result) => {
+			globals.del_item("_RUST_return")?;
+			Ok(result)
+		}
+		None => Ok(py.None().into_ref(py)),
+	}
+}
+
+/// The settings of a context that are applied while it runs code.
+pub(crate) struct Settings {
+	streams: Py<PyDict>,
+	warnings: Py<PyDict>,
+	#[cfg(feature = "log")]
+	forward_logging: bool,
+}
+
+impl Settings {
+	pub fn of(py: Python, context: &Context) -> Self {
+		Self {
+			streams: context.streams.clone_ref(py),
+			warnings: context.warnings.clone_ref(py),
+			#[cfg(feature = "log")]
+			forward_logging: context.forward_logging.load(std::sync::atomic::Ordering::Relaxed),
+		}
+	}
+
+	/// Call `f` with the settings applied, flushing the output before and after.
+	pub fn apply<'p, R>(&'p self, py: Python<'p>, f: impl FnOnce() -> PyResult<R>) -> PyResult<R> {
+		flush_before();
+		let result = (|| {
+			let _streams = ContextStreams::enter(py, self.streams.as_ref(py))?;
+			let _warnings = ContextWarnings::enter(py, self.warnings.as_ref(py))?;
+			#[cfg(feature = "log")]
+			let _logging = crate::logging::ForwardedLogging::enter(py, self.forward_logging)?;
+			f()
+		})();
+		flush_after(py);
+		result
+	}
+}
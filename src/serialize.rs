@@ -0,0 +1,582 @@
+//! Conversion between Python objects and Rust types that implement `serde`'s traits.
+
+use pyo3::{
+	exceptions::PyValueError,
+	types::{PyAny, PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyList, PyLong, PyMapping, PySequence, PyString, PyTuple},
+	FromPyObject, IntoPy, PyDowncastError, PyErr, PyObject, PyResult, Python, ToPyObject,
+};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+/// A value that is converted to and from Python using `serde`.
+///
+/// Structs and maps are converted to `dict`s, sequences to `list`s, tuples to
+/// `tuple`s, and unit enum variants to the name of the variant. Other enum
+/// variants are converted to a `dict` with the name of the variant as its only key.
+///
+/// This is available with the `serde` feature.
+///
+/// ```
+/// # use inline_python::{python, Context, Serde};
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// let c = Context::new();
+/// c.set("p", Serde(Point { x: 1.0, y: 2.0 }));
+/// c.run(python! {
+///     assert p == {"x": 1.0, "y": 2.0}
+///     q = {"x": p["y"], "y": p["x"]}
+/// });
+/// let Serde(q): Serde<Point> = c.get("q");
+/// assert_eq!(q.x, 2.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Serde<T>(pub T);
+
+/// Converting a `Serde<T>` to Python panics if `T` fails to serialize.
+///
+/// Use [`to_python`] to handle that error instead.
+impl<T: Serialize> ToPyObject for Serde<T> {
+	fn to_object(&self, py: Python) -> PyObject {
+		match to_python(py, &self.0) {
+			Ok(object) => object,
+			Err(e) => {
+				e.print(py);
+				panic!("Unable to serialize a `{}` to Python", std::any::type_name::<T>());
+			}
+		}
+	}
+}
+
+impl<T: Serialize> IntoPy<PyObject> for Serde<T> {
+	fn into_py(self, py: Python) -> PyObject {
+		self.to_object(py)
+	}
+}
+
+impl<'p, T: DeserializeOwned> FromPyObject<'p> for Serde<T> {
+	fn extract(object: &'p PyAny) -> PyResult<Self> {
+		from_python(object).map(Serde)
+	}
+}
+
+/// Convert a Rust value to a Python object using its `Serialize` implementation.
+///
+/// This is available with the `serde` feature.
+pub fn to_python<T: Serialize + ?Sized>(py: Python, value: &T) -> PyResult<PyObject> {
+	value.serialize(Serializer { py }).map_err(|e| e.0)
+}
+
+/// Convert a Python object to a Rust value using its `Deserialize` implementation.
+///
+/// This is available with the `serde` feature.
+pub fn from_python<T: DeserializeOwned>(object: &PyAny) -> PyResult<T> {
+	T::deserialize(Deserializer { object }).map_err(|e| e.0)
+}
+
+/// A Python exception, or a `serde` error converted to a Python `ValueError`.
+struct Error(PyErr);
+
+impl From<PyErr> for Error {
+	fn from(error: PyErr) -> Self {
+		Self(error)
+	}
+}
+
+impl From<PyDowncastError<'_>> for Error {
+	fn from(error: PyDowncastError) -> Self {
+		Self(error.into())
+	}
+}
+
+impl fmt::Debug for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Debug::fmt(&self.0, f)
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&self.0, f)
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Self(PyValueError::new_err(msg.to_string()))
+	}
+}
+
+impl de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Self(PyValueError::new_err(msg.to_string()))
+	}
+}
+
+struct Serializer<'p> {
+	py: Python<'p>,
+}
+
+/// Collects the elements of a sequence or tuple.
+struct SerializeSeq<'p> {
+	py: Python<'p>,
+	items: Vec<PyObject>,
+	/// The variant name for a tuple variant.
+	variant: Option<&'static str>,
+	tuple: bool,
+}
+
+/// Collects the entries of a map or struct.
+struct SerializeMap<'p> {
+	py: Python<'p>,
+	dict: &'p PyDict,
+	key: Option<PyObject>,
+	/// The variant name for a struct variant.
+	variant: Option<&'static str>,
+}
+
+impl<'p> Serializer<'p> {
+	/// Wrap a value in a `dict` with the variant name as its only key.
+	fn variant(&self, variant: &'static str, value: PyObject) -> Result<PyObject, Error> {
+		let dict = PyDict::new(self.py);
+		dict.set_item(variant, value)?;
+		Ok(dict.into())
+	}
+}
+
+impl<'p> ser::Serializer for Serializer<'p> {
+	type Ok = PyObject;
+	type Error = Error;
+	type SerializeSeq = SerializeSeq<'p>;
+	type SerializeTuple = SerializeSeq<'p>;
+	type SerializeTupleStruct = SerializeSeq<'p>;
+	type SerializeTupleVariant = SerializeSeq<'p>;
+	type SerializeMap = SerializeMap<'p>;
+	type SerializeStruct = SerializeMap<'p>;
+	type SerializeStructVariant = SerializeMap<'p>;
+
+	fn serialize_bool(self, v: bool) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_i8(self, v: i8) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_i16(self, v: i16) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_i32(self, v: i32) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_i64(self, v: i64) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_i128(self, v: i128) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_u8(self, v: u8) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_u16(self, v: u16) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_u32(self, v: u32) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_u64(self, v: u64) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_u128(self, v: u128) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_f32(self, v: f32) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_f64(self, v: f64) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_char(self, v: char) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_str(self, v: &str) -> Result<PyObject, Error> {
+		Ok(v.into_py(self.py))
+	}
+	fn serialize_bytes(self, v: &[u8]) -> Result<PyObject, Error> {
+		Ok(PyBytes::new(self.py, v).into())
+	}
+	fn serialize_none(self) -> Result<PyObject, Error> {
+		Ok(self.py.None())
+	}
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<PyObject, Error> {
+		value.serialize(self)
+	}
+	fn serialize_unit(self) -> Result<PyObject, Error> {
+		Ok(self.py.None())
+	}
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<PyObject, Error> {
+		Ok(self.py.None())
+	}
+	fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<PyObject, Error> {
+		Ok(variant.into_py(self.py))
+	}
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<PyObject, Error> {
+		value.serialize(self)
+	}
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		value: &T,
+	) -> Result<PyObject, Error> {
+		let value = value.serialize(Serializer { py: self.py })?;
+		self.variant(variant, value)
+	}
+	fn serialize_seq(self, len: Option<usize>) -> Result<SerializeSeq<'p>, Error> {
+		Ok(SerializeSeq {
+			py: self.py,
+			items: Vec::with_capacity(len.unwrap_or(0)),
+			variant: None,
+			tuple: false,
+		})
+	}
+	fn serialize_tuple(self, len: usize) -> Result<SerializeSeq<'p>, Error> {
+		Ok(SerializeSeq {
+			py: self.py,
+			items: Vec::with_capacity(len),
+			variant: None,
+			tuple: true,
+		})
+	}
+	fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeSeq<'p>, Error> {
+		self.serialize_tuple(len)
+	}
+	fn serialize_tuple_variant(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		len: usize,
+	) -> Result<SerializeSeq<'p>, Error> {
+		Ok(SerializeSeq {
+			py: self.py,
+			items: Vec::with_capacity(len),
+			variant: Some(variant),
+			tuple: true,
+		})
+	}
+	fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap<'p>, Error> {
+		Ok(SerializeMap {
+			py: self.py,
+			dict: PyDict::new(self.py),
+			key: None,
+			variant: None,
+		})
+	}
+	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap<'p>, Error> {
+		self.serialize_map(Some(len))
+	}
+	fn serialize_struct_variant(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		_len: usize,
+	) -> Result<SerializeMap<'p>, Error> {
+		Ok(SerializeMap {
+			py: self.py,
+			dict: PyDict::new(self.py),
+			key: None,
+			variant: Some(variant),
+		})
+	}
+}
+
+impl<'p> SerializeSeq<'p> {
+	fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.items.push(value.serialize(Serializer { py: self.py })?);
+		Ok(())
+	}
+
+	fn finish(self) -> Result<PyObject, Error> {
+		let value: PyObject = if self.tuple {
+			PyTuple::new(self.py, self.items).into()
+		} else {
+			PyList::new(self.py, self.items).into()
+		};
+		match self.variant {
+			Some(variant) => Serializer { py: self.py }.variant(variant, value),
+			None => Ok(value),
+		}
+	}
+}
+
+impl ser::SerializeSeq for SerializeSeq<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl ser::SerializeTuple for SerializeSeq<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl ser::SerializeTupleStruct for SerializeSeq<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl ser::SerializeTupleVariant for SerializeSeq<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl SerializeMap<'_> {
+	fn finish(self) -> Result<PyObject, Error> {
+		match self.variant {
+			Some(variant) => Serializer { py: self.py }.variant(variant, self.dict.into()),
+			None => Ok(self.dict.into()),
+		}
+	}
+}
+
+impl ser::SerializeMap for SerializeMap<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+		self.key = Some(key.serialize(Serializer { py: self.py })?);
+		Ok(())
+	}
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		let key = self.key.take().expect("serialize_value called before serialize_key");
+		self.dict.set_item(key, value.serialize(Serializer { py: self.py })?)?;
+		Ok(())
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl ser::SerializeStruct for SerializeMap<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.dict.set_item(key, value.serialize(Serializer { py: self.py })?)?;
+		Ok(())
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+impl ser::SerializeStructVariant for SerializeMap<'_> {
+	type Ok = PyObject;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.dict.set_item(key, value.serialize(Serializer { py: self.py })?)?;
+		Ok(())
+	}
+	fn end(self) -> Result<PyObject, Error> {
+		self.finish()
+	}
+}
+
+struct Deserializer<'p> {
+	object: &'p PyAny,
+}
+
+impl<'de, 'p> de::Deserializer<'de> for Deserializer<'p> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let object = self.object;
+		if object.is_none() {
+			visitor.visit_unit()
+		} else if let Ok(b) = object.downcast::<PyBool>() {
+			visitor.visit_bool(b.is_true())
+		} else if object.is_instance_of::<PyLong>() {
+			if let Ok(v) = object.extract::<i64>() {
+				visitor.visit_i64(v)
+			} else if let Ok(v) = object.extract::<u64>() {
+				visitor.visit_u64(v)
+			} else if let Ok(v) = object.extract::<i128>() {
+				visitor.visit_i128(v)
+			} else {
+				visitor.visit_u128(object.extract()?)
+			}
+		} else if let Ok(f) = object.downcast::<PyFloat>() {
+			visitor.visit_f64(f.value())
+		} else if let Ok(s) = object.downcast::<PyString>() {
+			visitor.visit_str(s.to_str()?)
+		} else if let Ok(b) = object.downcast::<PyBytes>() {
+			visitor.visit_bytes(b.as_bytes())
+		} else if let Ok(b) = object.downcast::<PyByteArray>() {
+			visitor.visit_byte_buf(b.to_vec())
+		} else if let Ok(dict) = object.downcast::<PyDict>() {
+			visitor.visit_map(MapDeserializer::new(dict.items().into()))
+		} else if let Ok(mapping) = object.downcast::<PyMapping>() {
+			visitor.visit_map(MapDeserializer::new(mapping.items()?.into()))
+		} else if let Ok(seq) = object.downcast::<PySequence>() {
+			visitor.visit_seq(SeqDeserializer { seq, index: 0 })
+		} else {
+			Err(Error(PyValueError::new_err(format!(
+				"unable to deserialize an object of type '{}'",
+				object.get_type().name()?
+			))))
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		if self.object.is_none() {
+			visitor.visit_none()
+		} else {
+			visitor.visit_some(self)
+		}
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		_variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Error> {
+		if let Ok(s) = self.object.downcast::<PyString>() {
+			return visitor.visit_enum(s.to_str()?.into_deserializer());
+		}
+		let dict = self.object.downcast::<PyDict>()?;
+		if dict.len() != 1 {
+			return Err(Error(PyValueError::new_err(
+				"expected a string, or a dict with one key, for an enum",
+			)));
+		}
+		let (variant, value) = dict.items().get_item(0)?.extract::<(&PyAny, &PyAny)>()?;
+		visitor.visit_enum(EnumDeserializer { variant, value })
+	}
+
+	serde::forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+		identifier ignored_any
+	}
+}
+
+struct SeqDeserializer<'p> {
+	seq: &'p PySequence,
+	index: usize,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_> {
+	type Error = Error;
+
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+		if self.index >= self.seq.len()? {
+			return Ok(None);
+		}
+		let object = self.seq.get_item(self.index)?;
+		self.index += 1;
+		seed.deserialize(Deserializer { object }).map(Some)
+	}
+}
+
+struct MapDeserializer<'p> {
+	items: &'p PySequence,
+	index: usize,
+	value: Option<&'p PyAny>,
+}
+
+impl<'p> MapDeserializer<'p> {
+	fn new(items: &'p PyAny) -> Self {
+		Self {
+			items: items.downcast().expect("items() should return a sequence"),
+			index: 0,
+			value: None,
+		}
+	}
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer<'_> {
+	type Error = Error;
+
+	fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+		if self.index >= self.items.len()? {
+			return Ok(None);
+		}
+		let (key, value) = self.items.get_item(self.index)?.extract::<(&PyAny, &PyAny)>()?;
+		self.index += 1;
+		self.value = Some(value);
+		seed.deserialize(Deserializer { object: key }).map(Some)
+	}
+
+	fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+		let object = self.value.take().expect("next_value_seed called before next_key_seed");
+		seed.deserialize(Deserializer { object })
+	}
+}
+
+struct EnumDeserializer<'p> {
+	variant: &'p PyAny,
+	value: &'p PyAny,
+}
+
+impl<'de, 'p> de::EnumAccess<'de> for EnumDeserializer<'p> {
+	type Error = Error;
+	type Variant = Deserializer<'p>;
+
+	fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Deserializer<'p>), Error> {
+		let variant = seed.deserialize(Deserializer { object: self.variant })?;
+		Ok((variant, Deserializer { object: self.value }))
+	}
+}
+
+impl<'de> VariantAccess<'de> for Deserializer<'_> {
+	type Error = Error;
+
+	fn unit_variant(self) -> Result<(), Error> {
+		de::Deserialize::deserialize(self)
+	}
+
+	fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+		seed.deserialize(self)
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_seq(self, visitor)
+	}
+
+	fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_map(self, visitor)
+	}
+}
@@ -0,0 +1,145 @@
+//! Running Python coroutines on an `asyncio` event loop, as Rust futures.
+
+use crate::run::Settings;
+use pyo3::{
+	sync::GILOnceCell,
+	types::{PyCFunction, PyDict, PyModule, PyTuple},
+	Py, PyAny, PyObject, PyResult, Python,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+/// Python code that runs coroutines on an event loop in a background thread.
+///
+/// Every step of a coroutine, from one suspension to the next, is run through
+/// a `step` function, which applies the settings of the context.
+///
+/// The result of the block is taken out of the globals in the same step of the
+/// `asyncio` task in which the block finishes, before any other block on the event
+/// loop can run, so concurrent blocks in the same context don't get each other's result.
+const EVENT_LOOP: &str = r#"
+import asyncio
+import threading
+
+_lock = threading.Lock()
+_loop = None
+
+def event_loop():
+	global _loop
+	with _lock:
+		if _loop is None:
+			_loop = asyncio.new_event_loop()
+			threading.Thread(target=_loop.run_forever, name="inline-python-asyncio", daemon=True).start()
+		return _loop
+
+class Steps:
+	def __init__(self, coroutine, step):
+		self._coroutine = coroutine
+		self._step = step
+
+	def __await__(self):
+		value, error = None, None
+		while True:
+			try:
+				if error is None:
+					yielded = self._step(lambda: self._coroutine.send(value))
+				else:
+					yielded = self._step(lambda: self._coroutine.throw(error))
+			except StopIteration as stop:
+				return stop.value
+			try:
+				value, error = (yield yielded), None
+			except BaseException as e:
+				value, error = None, e
+
+async def _run(steps, globals):
+	await steps
+	return globals.pop("_RUST_return", None)
+
+def start(coroutine, step, globals, done):
+	future = asyncio.run_coroutine_threadsafe(_run(Steps(coroutine, step), globals), event_loop())
+	future.add_done_callback(lambda _: done())
+	return future
+"#;
+
+/// State shared between the future and the callback that is called when the coroutine is done.
+#[derive(Default)]
+struct Shared {
+	done: AtomicBool,
+	waker: Mutex<Option<Waker>>,
+}
+
+/// A Python coroutine running on the event loop, as a Rust future.
+///
+/// Dropping it before it is done cancels the coroutine.
+pub(crate) struct PythonTask {
+	/// The `concurrent.futures.Future` of the coroutine.
+	future: PyObject,
+	shared: Arc<Shared>,
+	finished: bool,
+}
+
+impl PythonTask {
+	/// Start running the coroutine on the event loop, with the settings applied while it runs.
+	///
+	/// The output of the future is the result of the block, taken out of `globals`.
+	pub fn start(py: Python, coroutine: &PyAny, settings: Settings, globals: &PyDict) -> PyResult<Self> {
+		static EVENT_LOOP_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+		let module = EVENT_LOOP_MODULE.get_or_try_init(py, || -> PyResult<_> {
+			Ok(PyModule::from_code(py, EVENT_LOOP, "<inline-python>", "_inline_python_event_loop")?.into())
+		})?;
+		let step = PyCFunction::new_closure(
+			py,
+			None,
+			None,
+			move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<PyObject> {
+				let py = args.py();
+				let (f,): (&PyAny,) = args.extract()?;
+				settings.apply(py, || f.call0().map(Into::into))
+			},
+		)?;
+		let shared = Arc::new(Shared::default());
+		let shared2 = shared.clone();
+		let done = PyCFunction::new_closure(py, None, None, move |_args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<()> {
+			shared2.done.store(true, Ordering::Release);
+			if let Some(waker) = shared2.waker.lock().unwrap_or_else(PoisonError::into_inner).take() {
+				waker.wake();
+			}
+			Ok(())
+		})?;
+		let future = module.as_ref(py).getattr("start")?.call1((coroutine, step, globals, done))?.into();
+		Ok(Self {
+			future,
+			shared,
+			finished: false,
+		})
+	}
+}
+
+impl Future for PythonTask {
+	type Output = PyResult<PyObject>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<PyResult<PyObject>> {
+		*self.shared.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+		if !self.shared.done.load(Ordering::Acquire) {
+			return Poll::Pending;
+		}
+		self.finished = true;
+		Poll::Ready(Python::with_gil(|py| self.future.as_ref(py).call_method0("result").map(Into::into)))
+	}
+}
+
+impl Drop for PythonTask {
+	fn drop(&mut self) {
+		if !self.finished {
+			Python::with_gil(|py| {
+				if let Err(e) = self.future.as_ref(py).call_method0("cancel") {
+					e.print(py);
+				}
+			});
+		}
+	}
+}
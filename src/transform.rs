@@ -0,0 +1,47 @@
+use pyo3::{sync::GILOnceCell, types::PyModule, Py, PyObject, PyResult, Python};
+
+/// Python code to compile a `python!{}` block such that its result is stored in `_RUST_return`.
+///
+/// If the block contains a `return` statement outside of any function or
+/// class, the block is turned into the body of a function, and the return
+/// value of that function is the result.
+/// Otherwise, if the block ends in an expression, the value of that
+/// expression is the result.
+///
+/// If `parameters` are given, the block is always turned into the body of a
+/// function with those parameters, which is called with the global variables of
+/// the same names. The final expression is then returned from that function.
+///
+/// The block may use `await` outside of any function, which makes the
+/// resulting code object a coroutine. If it is turned into a function, that
+/// function is then an `async` function, which is awaited.
+///
+/// This is the same code the macro uses, from `macros/src/transform.py`. It is
+/// used to compile the code again when the embedded bytecode is for a
+/// different version of Python.
+const TRANSFORM: &str = inline_python_macros::transform_source!();
+
+/// Compile the Python code of a `python!{}` block, storing its result in `_RUST_return`.
+pub fn compile_block(py: Python, python: &str, filename: &str, parameters: Option<&[&str]>) -> PyResult<PyObject> {
+	Ok(transform_module(py)?
+		.getattr("compile_block")?
+		.call1((python, filename, parameters.map(<[&str]>::to_vec)))?
+		.into())
+}
+
+/// Compile a Python script that is loaded at runtime, storing the value of its final expression in `_RUST_return`.
+///
+/// Unlike a `python!{}` block, a script has plain Python semantics:
+/// `return` and `await` outside of a function are syntax errors.
+pub fn compile_script(py: Python, python: &str, filename: &str) -> PyResult<PyObject> {
+	Ok(transform_module(py)?.getattr("compile_script")?.call1((python, filename))?.into())
+}
+
+fn transform_module(py: Python<'_>) -> PyResult<&PyModule> {
+	static TRANSFORM_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+	TRANSFORM_MODULE
+		.get_or_try_init(py, || -> PyResult<_> {
+			Ok(PyModule::from_code(py, TRANSFORM, "<inline-python>", "_inline_python_transform")?.into())
+		})
+		.map(|module| module.as_ref(py))
+}
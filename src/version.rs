@@ -0,0 +1,96 @@
+use crate::run::EmbeddedCode;
+use pyo3::{sync::GILOnceCell, types::PyBytes, PyResult, Python};
+use std::fmt;
+use std::sync::RwLock;
+
+type Hook = Box<dyn Fn(&VersionMismatch) + Send + Sync>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Information about a `python!{}` block that was compiled for a different version of Python.
+///
+/// The bytecode of a `python!{}` block is generated at compile time by the
+/// Python interpreter that the macro finds. If the program is run with a
+/// different version of Python, the bytecode can not be used, and the block is
+/// compiled again from its source code instead.
+///
+/// See [`on_version_mismatch`] to get notified when this happens.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct VersionMismatch {
+	filename: &'static str,
+	line: usize,
+	compiled_version: &'static str,
+	runtime_version: String,
+}
+
+impl VersionMismatch {
+	/// The Rust source file containing the `python!{}` block.
+	pub fn filename(&self) -> &str {
+		self.filename
+	}
+
+	/// The line in the Rust source file that the Python code starts at.
+	pub fn line(&self) -> usize {
+		self.line
+	}
+
+	/// The version of Python that the block was compiled with, such as `3.11.4`.
+	pub fn compiled_version(&self) -> &str {
+		self.compiled_version
+	}
+
+	/// The version of Python that is running the block.
+	pub fn runtime_version(&self) -> &str {
+		&self.runtime_version
+	}
+}
+
+impl fmt::Display for VersionMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"python!{{...}} at {}:{} was compiled for Python {}, but is running on Python {}",
+			self.filename, self.line, self.compiled_version, self.runtime_version
+		)
+	}
+}
+
+/// Set a function to be called whenever a `python!{}` block is compiled again
+/// because it was compiled for a different version of Python.
+///
+/// This happens only the first time each block runs.
+/// Without a hook, this happens silently.
+/// Setting a new hook replaces the previous one.
+///
+/// ```no_run
+/// inline_python::on_version_mismatch(|mismatch| {
+///     eprintln!("warning: {}", mismatch);
+/// });
+/// ```
+pub fn on_version_mismatch(hook: impl Fn(&VersionMismatch) + Send + Sync + 'static) {
+	*HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(hook));
+}
+
+/// The magic number of the bytecode of the running Python interpreter.
+pub(crate) fn magic_number(py: Python<'_>) -> PyResult<&[u8]> {
+	static MAGIC_NUMBER: GILOnceCell<Vec<u8>> = GILOnceCell::new();
+	let magic = MAGIC_NUMBER.get_or_try_init(py, || -> PyResult<_> {
+		let magic = py.import("importlib.util")?.getattr("MAGIC_NUMBER")?;
+		Ok(magic.downcast::<PyBytes>()?.as_bytes().to_vec())
+	})?;
+	Ok(magic)
+}
+
+/// Call the hook set by [`on_version_mismatch`], if any.
+pub(crate) fn report_version_mismatch(py: Python, embedded: &EmbeddedCode) {
+	if let Some(hook) = &*HOOK.read().unwrap_or_else(|e| e.into_inner()) {
+		let version = py.version_info();
+		hook(&VersionMismatch {
+			filename: embedded.filename,
+			line: embedded.line,
+			compiled_version: embedded.python_version,
+			runtime_version: format!("{}.{}.{}", version.major, version.minor, version.patch),
+		});
+	}
+}
@@ -0,0 +1,195 @@
+//! Handling Python warnings in Rust.
+
+use pyo3::{
+	sync::GILOnceCell,
+	types::{PyCFunction, PyDict, PyModule, PyTuple},
+	Py, PyAny, PyObject, PyResult, Python,
+};
+use std::fmt;
+
+/// A warning issued by Python code, such as with `warnings.warn()`.
+///
+/// See [`Context::on_warning`](crate::Context::on_warning).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PyWarning {
+	category: String,
+	message: String,
+	filename: String,
+	line: usize,
+}
+
+impl PyWarning {
+	/// The name of the category of the warning, such as `DeprecationWarning`.
+	///
+	/// Categories that are not built in are prefixed by their module, such as `numpy.VisibleDeprecationWarning`.
+	pub fn category(&self) -> &str {
+		&self.category
+	}
+
+	/// The message of the warning.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+
+	/// The file that issued the warning.
+	///
+	/// For a `python!{}` block, this is the Rust source file.
+	pub fn filename(&self) -> &str {
+		&self.filename
+	}
+
+	/// The line in the file that issued the warning.
+	pub fn line(&self) -> usize {
+		self.line
+	}
+}
+
+impl fmt::Display for PyWarning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}: {}: {}", self.filename, self.line, self.category, self.message)
+	}
+}
+
+/// Python code that applies the warning settings of a context.
+///
+/// Filters at the front of `warnings.filters` and a wrapper around `warnings.showwarning`
+/// are installed once, and look up the settings of the running context in a context variable,
+/// which is separate for every thread and every `asyncio` task.
+/// They are installed again if other code removes them.
+const WARNINGS: &str = r#"
+import contextvars
+import importlib
+import warnings
+
+_run = contextvars.ContextVar("inline_python_warnings", default=None)
+
+def category_name(category):
+	if category.__module__ == "builtins":
+		return category.__qualname__
+	return category.__module__ + "." + category.__qualname__
+
+def category(name):
+	module, _, name = name.rpartition(".")
+	return getattr(importlib.import_module(module or "builtins"), name)
+
+class Run:
+	def __init__(self, hook, errors):
+		self.hook = hook
+		self.errors = errors
+		self.seen = set()
+
+class ErrorMatcher:
+	def __init__(self, name):
+		self._name = name
+
+	def match(self, text):
+		run = _run.get()
+		return run is not None and self._name in run.errors
+
+class HookMatcher:
+	def match(self, text):
+		run = _run.get()
+		return run is not None and run.hook is not None
+
+class ShowWarning:
+	def __init__(self, default):
+		self._default = default
+
+	def __call__(self, message, category, filename, lineno, file=None, line=None):
+		run = _run.get()
+		if run is None or run.hook is None:
+			return self._default(message, category, filename, lineno, file, line)
+		key = (str(message), category, filename, lineno)
+		if key not in run.seen:
+			run.seen.add(key)
+			run.hook(category_name(category), str(message), filename, lineno)
+
+_error_filters = {}
+_hook_filter = ("always", HookMatcher(), Warning, None, 0)
+
+def _filters_mutated():
+	mutated = getattr(warnings, "_filters_mutated", None)
+	if mutated is not None:
+		mutated()
+
+def install(errors):
+	for name in errors:
+		if name not in _error_filters:
+			_error_filters[name] = ("error", ErrorMatcher(name), category(name), None, 0)
+	entries = [*_error_filters.values(), _hook_filter]
+	filters = warnings.filters
+	if any(entry not in filters for entry in entries):
+		filters[:] = entries + [entry for entry in filters if entry not in entries]
+	if not isinstance(warnings.showwarning, ShowWarning):
+		warnings.showwarning = ShowWarning(warnings.showwarning)
+
+def enter(settings):
+	errors = frozenset(settings.get("errors", ()))
+	install(errors)
+	token = _run.set(Run(settings.get("hook"), errors))
+	# Forget which warnings were already shown, which the filters of the context might handle differently.
+	_filters_mutated()
+	return token
+
+def exit(token):
+	_run.reset(token)
+"#;
+
+fn warnings_module(py: Python<'_>) -> PyResult<&PyModule> {
+	static WARNINGS_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+	WARNINGS_MODULE
+		.get_or_try_init(py, || -> PyResult<_> {
+			Ok(PyModule::from_code(py, WARNINGS, "<inline-python>", "_inline_python_warnings")?.into())
+		})
+		.map(|module| module.as_ref(py))
+}
+
+/// Create the Python function that `warnings.showwarning` calls for a context.
+pub(crate) fn new_hook(py: Python<'_>, hook: impl Fn(PyWarning) + Send + 'static) -> PyResult<PyObject> {
+	let hook = PyCFunction::new_closure(py, None, None, move |args: &PyTuple, _kwargs: Option<&PyDict>| -> PyResult<()> {
+		let (category, message, filename, line) = args.extract()?;
+		hook(PyWarning {
+			category,
+			message,
+			filename,
+			line,
+		});
+		Ok(())
+	})?;
+	Ok(hook.into())
+}
+
+/// Applies the warning settings of a context, until dropped.
+///
+/// The settings are a dict with an optional `"hook"` for `warnings.showwarning`,
+/// and an optional list of names of categories that are turned into errors as `"errors"`.
+///
+/// This only affects the current thread or `asyncio` task.
+pub(crate) struct ContextWarnings<'p> {
+	/// The module and the token to restore the previous settings.
+	entered: Option<(&'p PyModule, &'p PyAny)>,
+}
+
+impl<'p> ContextWarnings<'p> {
+	pub fn enter(py: Python<'p>, settings: &'p PyDict) -> PyResult<Self> {
+		if settings.is_empty() {
+			return Ok(Self { entered: None });
+		}
+		let module = warnings_module(py)?;
+		let token = module.getattr("enter")?.call1((settings,))?;
+		Ok(Self {
+			entered: Some((module, token)),
+		})
+	}
+}
+
+impl Drop for ContextWarnings<'_> {
+	fn drop(&mut self) {
+		if let Some((module, token)) = self.entered {
+			if let Err(e) = module.getattr("exit").and_then(|exit| exit.call1((token,))) {
+				e.print(module.py());
+			}
+		}
+	}
+}
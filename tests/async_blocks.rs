@@ -0,0 +1,163 @@
+use inline_python::{python, Context};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+	fn wake(self: Arc<Self>) {
+		self.0.unpark();
+	}
+}
+
+fn waker() -> Waker {
+	Waker::from(Arc::new(ThreadWaker(std::thread::current())))
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+	let mut future = Box::pin(future);
+	let waker = waker();
+	let mut cx = TaskContext::from_waker(&waker);
+	loop {
+		if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+			return output;
+		}
+		std::thread::park();
+	}
+}
+
+#[test]
+fn top_level_await() {
+	let c = Context::new();
+	let mut count = 1;
+	block_on(c.run_async(python! {
+		import asyncio
+
+		async def fetch():
+			await asyncio.sleep(0.01)
+			return "data"
+
+		data = await fetch()
+		for i in range(3):
+			await asyncio.sleep(0)
+			'mut count += 1
+	}));
+	assert_eq!(c.get::<String>("data"), "data");
+	assert_eq!(count, 4);
+}
+
+#[test]
+fn return_and_errors() {
+	let c = Context::new();
+	block_on(c.run_async(python! {
+		import asyncio
+		await asyncio.sleep(0)
+		if True:
+			return
+		assert False
+	}));
+	let error = block_on(c.try_run_async(python! {
+		import asyncio
+		await asyncio.sleep(0)
+		raise ValueError("oops")
+	}))
+	.unwrap_err();
+	assert_eq!(error.type_name(), "ValueError");
+
+	// Blocks without await also work.
+	block_on(c.run_async(python! {
+		x = 5
+	}));
+	assert_eq!(c.get::<i32>("x"), 5);
+}
+
+#[test]
+fn concurrent_blocks() {
+	let c = Context::new();
+	let mut a = Box::pin(c.run_async(python! {
+		import asyncio
+		await asyncio.sleep(0.01)
+		order = ["a"]
+		await asyncio.sleep(0.05)
+		order.append("a")
+		"a"
+	}));
+	let mut b = Box::pin(c.run_async(python! {
+		import asyncio
+		await asyncio.sleep(0.02)
+		order.append("b")
+		"b"
+	}));
+	let waker = waker();
+	let mut cx = TaskContext::from_waker(&waker);
+	let (mut a_done, mut b_done) = (false, false);
+	while !(a_done && b_done) {
+		a_done = a_done || a.as_mut().poll(&mut cx).is_ready();
+		b_done = b_done || b.as_mut().poll(&mut cx).is_ready();
+		std::thread::park_timeout(Duration::from_millis(1));
+	}
+	assert_eq!(c.get::<Vec<String>>("order"), ["a", "b", "a"]);
+	// Each task takes its own result, and none is left for the next block.
+	c.run(python! {
+		assert "_RUST_return" not in globals()
+	});
+}
+
+#[test]
+fn does_not_block_and_cancels_on_drop() {
+	let c = Context::new();
+	c.run(python! {
+		state = "idle"
+	});
+	let mut future = Box::pin(c.run_async(python! {
+		import asyncio
+		state = "running"
+		try:
+			await asyncio.sleep(60)
+		except asyncio.CancelledError:
+			state = "cancelled"
+			raise
+	}));
+	let waker = waker();
+	assert!(future.as_mut().poll(&mut TaskContext::from_waker(&waker)).is_pending());
+	let deadline = Instant::now() + Duration::from_secs(10);
+	while c.get::<String>("state") != "running" {
+		assert!(Instant::now() < deadline);
+		std::thread::sleep(Duration::from_millis(1));
+	}
+	drop(future);
+	while c.get::<String>("state") != "cancelled" {
+		assert!(Instant::now() < deadline);
+		std::thread::sleep(Duration::from_millis(1));
+	}
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for Buffer {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn context_settings_apply() {
+	let output = Buffer::default();
+	let c = Context::new();
+	c.set_stdout(output.clone());
+	block_on(c.run_async(python! {
+		import asyncio
+		print("before")
+		await asyncio.sleep(0.01)
+		print("after")
+	}));
+	assert_eq!(*output.0.lock().unwrap(), b"before\nafter\n");
+}
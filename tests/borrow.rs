@@ -0,0 +1,150 @@
+// The code generated for `'borrow` must not need `unsafe`.
+#![forbid(unsafe_code)]
+
+use inline_python::pyo3::{
+	exceptions::PyAttributeError,
+	types::{PyAny, PyDict, PyTuple},
+	PyObject, PyResult, Python, ToPyObject,
+};
+use inline_python::{python, Borrowable, Borrowed, Context};
+
+struct Model {
+	name: String,
+	weights: Vec<f64>,
+}
+
+impl Borrowable for Model {
+	fn get_attr(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+		match name {
+			"name" => Ok(self.name.to_object(py)),
+			"weights" => Ok(self.weights.to_object(py)),
+			_ => Err(PyAttributeError::new_err(name.to_string())),
+		}
+	}
+
+	fn set_attr(&mut self, _py: Python<'_>, name: &str, value: &PyAny) -> PyResult<()> {
+		match name {
+			"name" => self.name = value.extract()?,
+			_ => return Err(PyAttributeError::new_err(name.to_string())),
+		}
+		Ok(())
+	}
+
+	fn has_method(&self, name: &str) -> bool {
+		matches!(name, "total" | "scale")
+	}
+
+	fn call_method(&self, py: Python<'_>, name: &str, _args: &PyTuple, _kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		match name {
+			"total" => Ok(self.weights.iter().sum::<f64>().to_object(py)),
+			_ => Err(PyAttributeError::new_err(name.to_string())),
+		}
+	}
+
+	fn call_method_mut(&mut self, py: Python<'_>, name: &str, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+		match name {
+			"scale" => {
+				let (factor,): (f64,) = args.extract()?;
+				self.weights.iter_mut().for_each(|w| *w *= factor);
+				Ok(py.None())
+			}
+			_ => self.call_method(py, name, args, kwargs),
+		}
+	}
+}
+
+fn model() -> Model {
+	Model {
+		name: "model".into(),
+		weights: vec![1.0, 2.0],
+	}
+}
+
+#[test]
+fn borrow_in_block() {
+	let mut model = model();
+	python! {
+		assert 'borrow model.name == "model"
+		assert 'borrow model.total() == 3.0
+		try:
+			'borrow model.name = "other"
+			assert False
+		except AttributeError:
+			pass
+	}
+	python! {
+		m = 'borrow mut model
+		m.scale(2)
+		m.name = "scaled"
+		assert m.weights == [2.0, 4.0]
+		assert m.total() == 6.0
+	}
+	assert_eq!(model.name, "scaled");
+	assert_eq!(model.weights, [2.0, 4.0]);
+}
+
+#[test]
+fn use_after_borrow() {
+	let model = model();
+	let c: Context = python! {
+		m = 'borrow model
+		total = m.total
+	};
+	let error = c.try_run(python! { m.name }).unwrap_err();
+	assert_eq!(error.type_name(), "ReferenceError");
+	let error = c.try_run(python! { total() }).unwrap_err();
+	assert_eq!(error.type_name(), "ReferenceError");
+}
+
+#[test]
+fn borrow_scope() {
+	let c = Context::new();
+	let mut model = model();
+	Borrowed::scope_mut(&mut model, |model| {
+		c.set("model", model);
+		c.run(python! {
+			model.scale(3)
+			try:
+				model.missing()
+				assert False
+			except AttributeError:
+				pass
+			assert hasattr(model, "total")
+			assert not hasattr(model, "missing")
+			assert not hasattr(model, "__array__")
+		});
+	});
+	assert_eq!(model.weights, [3.0, 6.0]);
+	assert_eq!(c.try_run(python! { model.weights }).unwrap_err().type_name(), "ReferenceError");
+}
+
+#[test]
+fn borrow_in_context() {
+	let c = Context::new();
+	let mut values = vec![1, 2, 3];
+	c.run(python! {
+		v = 'borrow mut values
+		v.append(4)
+	});
+	assert_eq!(values, [1, 2, 3, 4]);
+	c.try_run(python! {
+		'borrow mut values[0] = 0
+	})
+	.unwrap();
+	assert_eq!(values, [0, 2, 3, 4]);
+	assert_eq!(c.try_run(python! { len(v) }).unwrap_err().type_name(), "ReferenceError");
+}
+
+#[test]
+fn borrow_from_thread() {
+	let model = model();
+	python! {
+		import threading
+		m = 'borrow model
+		results = []
+		thread = threading.Thread(target=lambda: results.append(m.total()))
+		thread.start()
+		thread.join()
+		assert results == [3.0]
+	}
+}
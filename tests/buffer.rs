@@ -0,0 +1,117 @@
+use inline_python::{python, Context, SliceBuffer};
+
+#[test]
+fn read_buffer() {
+	let data = [1.5f64, 2.5, -1.0];
+	let ints = [1i32, -2, 3];
+	python! {
+		view = memoryview('buffer data)
+		assert view.format == "d"
+		assert view.readonly
+		assert view.tolist() == [1.5, 2.5, -1.0]
+		assert view.nbytes == 24
+		assert bytes('buffer ints) == memoryview('buffer ints).cast("B").tobytes()
+		assert list(memoryview('buffer ints)[1:]) == [-2, 3]
+		try:
+			view[0] = 1.0
+			assert False
+		except TypeError:
+			pass
+		view.release()
+	}
+}
+
+#[test]
+fn write_buffer() {
+	let mut data = vec![0u16; 4];
+	let slice = &mut data[1..];
+	python! {
+		import struct
+		view = memoryview('buffer mut slice)
+		assert not view.readonly
+		view[0] = 7
+		struct.pack_into("=H", view.cast("B"), 4, 9)
+		del view
+	}
+	assert_eq!(data, [0, 7, 0, 9]);
+}
+
+#[test]
+fn buffer_in_context() {
+	let c = Context::new();
+	let data = [1u8, 2, 3];
+	let mut out = [0i32; 2];
+	c.run(python! {
+		total = sum(memoryview('buffer data))
+	});
+	assert_eq!(c.get::<u32>("total"), 6);
+	c.try_run(python! {
+		memoryview('buffer mut out)[1] = total
+	})
+	.unwrap();
+	assert_eq!(out, [0, 6]);
+}
+
+#[test]
+fn buffer_is_not_copied() {
+	let mut data = [1u8, 2, 3];
+	let address = data.as_ptr() as usize;
+	python! {
+		import ctypes
+		with memoryview('buffer mut data) as view:
+			first = ctypes.c_uint8.from_buffer(view)
+			assert ctypes.addressof(first) == 'address
+			first.value = 9
+			del first
+	}
+	assert_eq!(data, [9, 2, 3]);
+}
+
+#[test]
+fn buffer_is_invalidated() {
+	let data = [1u8, 2, 3];
+	let c: Context = python! {
+		buffer = 'buffer data
+		assert len(memoryview(buffer)) == 3
+	};
+	let error = c
+		.try_run(python! {
+			memoryview(buffer)
+		})
+		.unwrap_err();
+	assert_eq!(error.type_name(), "BufferError");
+}
+
+#[test]
+fn view_outliving_buffer_aborts() {
+	// A view that is still alive would refer to the slice after the block.
+	if std::env::var_os("INLINE_PYTHON_LEAK_VIEW").is_some() {
+		let data = [1u8, 2, 3];
+		let _c: Context = python! {
+			view = memoryview('buffer data)
+		};
+		return;
+	}
+	let output = std::process::Command::new(std::env::current_exe().unwrap())
+		.args(["view_outliving_buffer_aborts", "--exact", "--nocapture"])
+		.env("INLINE_PYTHON_LEAK_VIEW", "1")
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("BufferError: 1 view(s) of a Rust slice are still alive"));
+}
+
+#[test]
+fn buffer_scope() {
+	let c = Context::new();
+	let mut data = [1i64, 2, 3];
+	SliceBuffer::scope_mut(&mut data, |buffer| {
+		c.set("data", buffer);
+		c.run(python! {
+			with memoryview(data) as view:
+				view[2] = sum(view)
+		});
+	});
+	assert_eq!(data, [1, 2, 6]);
+	assert_eq!(c.try_run(python! { memoryview(data) }).unwrap_err().type_name(), "BufferError");
+}
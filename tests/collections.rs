@@ -0,0 +1,100 @@
+use inline_python::{python, Borrowed, Context};
+use std::collections::{BTreeMap, HashMap};
+
+#[test]
+fn vec_view() {
+	let mut values = vec![1.0f64, 2.0, 3.0];
+	python! {
+		import collections.abc
+		v = 'borrow values
+		assert isinstance(v, collections.abc.Sequence)
+		assert len(v) == 3 and v[-1] == 3.0 and v[::2] == [1.0, 3.0]
+		assert list(v) == [1.0, 2.0, 3.0] and 2.0 in v
+		try:
+			v[0] = 5.0
+			assert False
+		except TypeError:
+			pass
+	}
+	python! {
+		v = 'borrow mut values
+		v[0] = 5
+		v.extend([6.0, 7.0])
+		v.insert(0, 0.5)
+		v.remove(2.0)
+		v.reverse()
+		try:
+			v.append("text")
+			assert False
+		except TypeError:
+			pass
+		try:
+			v[10]
+			assert False
+		except IndexError:
+			pass
+	}
+	assert_eq!(values, [7.0, 6.0, 3.0, 5.0, 0.5]);
+}
+
+#[test]
+fn map_view() {
+	let mut index: HashMap<String, u32> = HashMap::new();
+	index.insert("a".into(), 1);
+	index.insert("b".into(), 2);
+	let c = Context::new();
+	Borrowed::scope_mut(&mut index, |index| {
+		c.set("index", index);
+		c.run(python! {
+			assert index["a"] == 1 and index.get("c") is None and 5 not in index
+			assert sorted(index.items()) == [("a", 1), ("b", 2)]
+			index["c"] = index.pop("a") + 10
+			selected = {k: v for k, v in index.items() if v > 5}
+		});
+	});
+	assert_eq!(index.len(), 2);
+	assert_eq!(index["c"], 11);
+	assert_eq!(
+		c.get::<HashMap<String, u32>>("selected"),
+		index.iter().filter(|(_, v)| **v > 5).map(|(k, v)| (k.clone(), *v)).collect()
+	);
+	assert_eq!(c.try_run(python! { len(index) }).unwrap_err().type_name(), "ReferenceError");
+
+	let mut sorted: BTreeMap<i32, String> = (0..4).map(|i| (i, i.to_string())).collect();
+	python! {
+		m = 'borrow mut sorted
+		assert list(m) == [0, 1, 2, 3]
+		del m[2]
+		m.update({10: "ten"})
+		try:
+			del m[2]
+			assert False
+		except KeyError:
+			pass
+	}
+	assert_eq!(sorted.keys().copied().collect::<Vec<_>>(), [0, 1, 3, 10]);
+}
+
+#[test]
+fn map_iteration() {
+	let mut sorted: BTreeMap<i32, i32> = (0..3).map(|i| (i, i * i)).collect();
+	let c = Context::new();
+	Borrowed::scope_mut(&mut sorted, |sorted| {
+		c.set("m", sorted);
+		c.run(python! {
+			keys = m.keys()
+			m[5] = 25
+			assert list(keys) == [0, 1, 2, 5]
+			it = iter(m)
+			assert next(it) == 0
+			m[6] = 36
+			try:
+				next(it)
+				assert False
+			except RuntimeError:
+				pass
+			kept = iter(m)
+		});
+	});
+	assert_eq!(c.try_run(python! { next(kept) }).unwrap_err().type_name(), "ReferenceError");
+}
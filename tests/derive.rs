@@ -0,0 +1,61 @@
+use inline_python::{python, Context, FromContext, IntoContext};
+
+#[derive(FromContext, IntoContext, Debug, PartialEq)]
+struct Stats {
+	mean: f64,
+	pub labels: Vec<String>,
+	#[context(name = "n")]
+	count: usize,
+}
+
+#[test]
+fn extract_and_set_struct() {
+	let c: Context = python! {
+		mean = 2.5
+		labels = ["a", "b"]
+		n = 2
+	};
+	let stats: Stats = c.extract();
+	assert_eq!(
+		stats,
+		Stats {
+			mean: 2.5,
+			labels: vec!["a".into(), "b".into()],
+			count: 2
+		}
+	);
+
+	let c = Context::new();
+	c.set_all(stats);
+	c.run(python! {
+		assert mean == 2.5
+		assert labels == ["a", "b"]
+		assert n == 2
+	});
+}
+
+#[test]
+fn extract_errors() {
+	let c: Context = python! {
+		mean = "not a number"
+		n = 2
+	};
+	let error = c.try_extract::<Stats>().err().unwrap();
+	assert_eq!(error.type_name(), "TypeError");
+	assert!(error.message().contains("(2 errors)"));
+	assert!(error.message().contains("unable to convert 'mean' to `f64`"));
+	assert!(error.message().contains("name 'labels' is not defined"));
+}
+
+#[derive(FromContext)]
+struct Fields {
+	fields: i32,
+}
+
+#[test]
+fn field_names_are_hygienic() {
+	let c: Context = python! {
+		fields = 1
+	};
+	assert_eq!(c.extract::<Fields>().fields, 1);
+}
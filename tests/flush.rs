@@ -0,0 +1,42 @@
+use inline_python::{python, Context};
+use std::process::Command;
+
+const CHILD: &str = "INLINE_PYTHON_TEST_FLUSH_CHILD";
+
+/// Runs in a child process, with stdout piped to the parent.
+#[test]
+fn print_in_order() {
+	if std::env::var_os(CHILD).is_none() {
+		return;
+	}
+	print!("<1>");
+	python! {
+		print("<2>", end="")
+	}
+	print!("<3>");
+	let c = Context::new();
+	c.run(python! {
+		print("<4>")
+	});
+	println!("<5>");
+}
+
+#[test]
+fn piped_output_is_in_order() {
+	let output = Command::new(std::env::current_exe().unwrap())
+		.args(["--exact", "print_in_order", "--nocapture", "--test-threads=1"])
+		.env(CHILD, "1")
+		.env_remove("PYTHONUNBUFFERED")
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let positions: Vec<usize> = (1..=5)
+		.map(|i| {
+			stdout
+				.find(&format!("<{}>", i))
+				.unwrap_or_else(|| panic!("<{}> missing in {:?}", i, stdout))
+		})
+		.collect();
+	assert!(positions.windows(2).all(|w| w[0] < w[1]), "out of order: {:?}", stdout);
+}
@@ -0,0 +1,50 @@
+use inline_python::pyo3::exceptions::PyValueError;
+use inline_python::pyo3::PyErr;
+use inline_python::{python, Context};
+use std::sync::mpsc;
+
+#[test]
+fn add_closure() {
+	let (sender, receiver) = mpsc::channel();
+	let c = Context::new();
+	c.add_fn("send", move |message: String| sender.send(message).unwrap());
+	c.add_fn("answer", || 42);
+	c.run(python! {
+		send("hello")
+		send(str(answer()))
+	});
+	assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["hello", "42"]);
+}
+
+#[test]
+fn closure_errors() {
+	let c = Context::new();
+	c.add_fn("half", |x: i32| -> Result<i32, PyErr> {
+		if x % 2 == 0 {
+			Ok(x / 2)
+		} else {
+			Err(PyValueError::new_err(format!("{} is odd", x)))
+		}
+	});
+	c.run(python! {
+		assert half(4) == 2
+
+		try:
+			half(3)
+			assert False
+		except ValueError as e:
+			assert str(e) == "3 is odd"
+
+		try:
+			half(1, 2)
+			assert False
+		except TypeError as e:
+			assert str(e) == "function takes 1 positional argument but 2 were given"
+
+		try:
+			half("4")
+			assert False
+		except TypeError:
+			pass
+	});
+}
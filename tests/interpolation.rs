@@ -0,0 +1,57 @@
+use inline_python::python;
+
+struct Config {
+	threshold: f64,
+	name: String,
+}
+
+#[test]
+fn interpolate_expressions() {
+	let config = Config {
+		threshold: 0.5,
+		name: String::from("test"),
+	};
+	let mut evaluated = 0;
+	let result: bool = python! {
+		names = ['_(config.name.clone()), '_(config.name.to_uppercase())]
+		above = [x for x in [0.1, 0.6, 0.9] if x > '_(config.threshold)]
+		names == ["test", "TEST"] and above == [0.6, 0.9] and '_{ evaluated += 1; evaluated } == 1
+	};
+	assert!(result);
+	assert_eq!(evaluated, 1);
+}
+
+#[test]
+fn write_back_variables() {
+	let mut count = 1;
+	let mut names = vec![String::from("a")];
+	let step = 2;
+	inline_python::Context::new().run(python! {
+		'mut count += 'step
+		'mut names.append("b")
+	});
+	assert_eq!(count, 3);
+	assert_eq!(names, ["a", "b"]);
+
+	let doubled: bool = python! {
+		if 'mut count > 100:
+			return False
+		'mut count *= 2
+		return True
+	};
+	assert!(doubled);
+	assert_eq!(count, 6);
+}
+
+#[test]
+fn write_back_error() {
+	let mut count = 1;
+	let error = inline_python::Context::new()
+		.try_run(python! {
+			'mut count = "not a number"
+		})
+		.unwrap_err();
+	assert_eq!(error.type_name(), "TypeError");
+	assert!(error.message().contains("'mut count at tests/interpolation.rs:"));
+	assert_eq!(count, 1);
+}
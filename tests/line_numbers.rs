@@ -0,0 +1,29 @@
+use inline_python::{python, Context};
+
+#[test]
+fn traceback_points_at_rust_source() {
+	let c = Context::new();
+	let line = line!() + 5;
+	let error = c
+		.try_run(python! {
+			def fail():
+				x = 1
+				raise ValueError(x)
+			fail()
+		})
+		.unwrap_err();
+	let traceback = error.traceback().unwrap();
+	assert!(traceback.contains(&format!("{}\", line {}, in fail", file!(), line)));
+	assert!(traceback.contains(&format!("{}\", line {}, in <module>", file!(), line + 1)));
+}
+
+#[test]
+fn frame_line_numbers() {
+	let c = Context::new();
+	let line = line!() + 3;
+	c.run(python! {
+		import inspect
+		lineno = inspect.currentframe().f_lineno
+	});
+	assert_eq!(c.get::<u32>("lineno"), line);
+}
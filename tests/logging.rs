@@ -0,0 +1,117 @@
+use inline_python::{python, Context};
+use std::sync::Mutex;
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+	level: log::Level,
+	target: String,
+	message: String,
+	file: String,
+	line: u32,
+}
+
+static ENTRIES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+struct Logger;
+
+impl log::Log for Logger {
+	fn enabled(&self, _: &log::Metadata) -> bool {
+		true
+	}
+
+	fn log(&self, record: &log::Record) {
+		ENTRIES.lock().unwrap().push(Entry {
+			level: record.level(),
+			target: record.target().to_string(),
+			message: record.args().to_string(),
+			file: record.file().unwrap_or_default().to_string(),
+			line: record.line().unwrap_or_default(),
+		});
+	}
+
+	fn flush(&self) {}
+}
+
+fn take_entries() -> Vec<Entry> {
+	std::mem::take(&mut ENTRIES.lock().unwrap())
+}
+
+#[test]
+fn forward_logging() {
+	log::set_logger(&Logger).unwrap();
+	log::set_max_level(log::LevelFilter::Info);
+
+	// Only while the context runs code.
+	let c = Context::new();
+	c.forward_logging();
+	let line = line!() + 6;
+	c.run(python! {
+		import logging
+		// The level of the root logger is not changed.
+		assert logging.getLogger().level == logging.WARNING
+		logging.getLogger().setLevel(logging.DEBUG)
+		logging.getLogger("plugin").info("hello %s", "world")
+		logging.getLogger("plugin.sub").debug("filtered by the Rust level")
+	});
+	python! {
+		import logging
+		logging.getLogger("plugin").error("not forwarded")
+	}
+	assert_eq!(
+		take_entries(),
+		[Entry {
+			level: log::Level::Info,
+			target: "plugin".into(),
+			message: "hello world".into(),
+			file: file!().into(),
+			line,
+		}]
+	);
+
+	// The handlers of the Python loggers are left alone.
+	python! {
+		import io, logging
+		stream = io.StringIO()
+		logging.basicConfig(stream=stream, format="%(message)s")
+		logging.getLogger("plugin").error("configured")
+		assert stream.getvalue() == "configured\n"
+	}
+	c.run(python! {
+		import io, logging
+		stream = io.StringIO()
+		handler = logging.StreamHandler(stream)
+		logging.getLogger().addHandler(handler)
+		logging.getLogger("plugin").info("both")
+		logging.getLogger().removeHandler(handler)
+		assert stream.getvalue() == "both\n"
+	});
+	let entries = take_entries();
+	let entries: Vec<_> = entries.iter().map(|e| (e.level, &e.target[..], &e.message[..])).collect();
+	assert_eq!(entries, [(log::Level::Info, "plugin", "both")]);
+
+	// For all Python code.
+	inline_python::forward_logging();
+	python! {
+		import logging
+		logging.warning("one")
+		logging.getLogger("plugin").critical("two")
+	}
+	let entries = take_entries();
+	let entries: Vec<_> = entries.iter().map(|e| (e.level, &e.target[..], &e.message[..])).collect();
+	assert_eq!(entries, [(log::Level::Warn, "root", "one"), (log::Level::Error, "plugin", "two")]);
+
+	// The Rust level is checked when a record is logged.
+	log::set_max_level(log::LevelFilter::Debug);
+	python! {
+		import logging
+		logging.getLogger("plugin").debug("three")
+	}
+	log::set_max_level(log::LevelFilter::Off);
+	python! {
+		import logging
+		logging.getLogger("plugin").critical("not forwarded")
+	}
+	let entries = take_entries();
+	let entries: Vec<_> = entries.iter().map(|e| (e.level, &e.target[..], &e.message[..])).collect();
+	assert_eq!(entries, [(log::Level::Debug, "plugin", "three")]);
+}
@@ -0,0 +1,34 @@
+use inline_python::pyo3::{types::PyModule, Py, Python};
+use inline_python::{python, Context};
+
+fn make_module(py: Python) -> Py<PyModule> {
+	let module = PyModule::new(py, "fast").unwrap();
+	module.add("five", 5).unwrap();
+	let submodule = PyModule::new(py, "sub").unwrap();
+	submodule.add("six", 6).unwrap();
+	module.add_submodule(submodule).unwrap();
+	module.into()
+}
+
+#[test]
+fn add_module() {
+	let c = Context::new();
+	c.add_module(&make_module);
+	c.run(python! {
+		assert fast.five == 5
+		assert fast.sub.six == 6
+	});
+}
+
+#[test]
+fn import_module() {
+	let c = Context::new();
+	c.add_module_as("mycrate.fast", &make_module);
+	c.run(python! {
+		import mycrate.fast
+		from mycrate.fast.sub import six
+		assert mycrate.fast.five == 5
+		assert mycrate.fast.__name__ == "mycrate.fast"
+		assert six == 6
+	});
+}
@@ -0,0 +1,196 @@
+use inline_python::{python, CapturedOutput, Context};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+	fn contents(&self) -> String {
+		String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+	}
+}
+
+impl Write for Buffer {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn run_capturing() {
+	let c = Context::new();
+	let output = c.run_capturing(python! {
+		import sys
+		print("out")
+		print("err", file=sys.stderr)
+		sys.stdout.write("ünïcödé")
+	});
+	assert_eq!(
+		output,
+		CapturedOutput {
+			stdout: "out\nünïcödé".into(),
+			stderr: "err\n".into(),
+		}
+	);
+}
+
+#[test]
+fn run_capturing_error() {
+	let c = Context::new();
+	let error = c
+		.try_run_capturing(python! {
+			print("before")
+			raise ValueError("oops")
+		})
+		.unwrap_err();
+	assert_eq!(error.type_name(), "ValueError");
+	// The streams are restored after a failure.
+	let output = c.run_capturing(python! {
+		print("after")
+	});
+	assert_eq!(output.stdout, "after\n");
+}
+
+#[test]
+fn context_streams() {
+	let (out, err) = (Buffer::default(), Buffer::default());
+	let c = Context::new();
+	c.set_stdout(out.clone());
+	c.set_stderr(err.clone());
+	c.run(python! {
+		import sys
+		print("one")
+		print("two", file=sys.stderr)
+	});
+	c.run(python! {
+		print("three")
+	});
+	assert_eq!(out.contents(), "one\nthree\n");
+	assert_eq!(err.contents(), "two\n");
+
+	// Capturing takes precedence over the streams of the context, and restores them.
+	let output = c.run_capturing(python! {
+		print("captured")
+	});
+	assert_eq!(output.stdout, "captured\n");
+	c.run(python! {
+		print("four")
+	});
+	assert_eq!(out.contents(), "one\nthree\nfour\n");
+}
+
+#[test]
+fn write_requires_str() {
+	let c = Context::new();
+	c.set_stdout(Buffer::default());
+	c.run(python! {
+		import sys
+		try:
+			sys.stdout.write(b"bytes")
+			assert False
+		except TypeError:
+			pass
+	});
+}
+
+/// A reader that returns at most one byte per read, like an interactive stream.
+struct Trickle<R>(R);
+
+impl<R: Read> Read for Trickle<R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = buf.len().min(1);
+		self.0.read(&mut buf[..n])
+	}
+}
+
+#[test]
+fn context_stdin() {
+	let c = Context::new();
+	c.set_stdin(Trickle("first\nsecond\nthird\nfourth\nrest".as_bytes()));
+	c.run(python! {
+		import sys
+		assert input() == "first"
+		assert sys.stdin.readline() == "second\n"
+		assert sys.stdin.read(3) == "thi"
+		lines = list(sys.stdin)
+	});
+	assert_eq!(c.get::<Vec<String>>("lines"), ["rd\n", "fourth\n", "rest"]);
+	c.run(python! {
+		assert sys.stdin.read() == ""
+		try:
+			input()
+			assert False
+		except EOFError:
+			pass
+	});
+}
+
+#[test]
+fn global_stdin() {
+	inline_python::set_stdin("ünïcödé\n".as_bytes());
+	let line: String = python! {
+		import sys
+		sys.stdin.readline()
+	};
+	assert_eq!(line, "ünïcödé\n");
+
+	inline_python::pyo3::Python::with_gil(|py| inline_python::try_set_stdin_with_gil(py, "again\n".as_bytes())).unwrap();
+	let line: String = python! {
+		import sys
+		sys.stdin.readline()
+	};
+	assert_eq!(line, "again\n");
+}
+
+#[test]
+fn streams_in_threads() {
+	let threads: Vec<_> = (0..4)
+		.map(|i| {
+			std::thread::spawn(move || {
+				let out = Buffer::default();
+				let c = Context::new();
+				c.set_stdout(out.clone());
+				c.run(python! {
+					import time
+					for _ in range(20):
+						print('i)
+						time.sleep(0.001)
+				});
+				out.contents()
+			})
+		})
+		.collect();
+	for (i, thread) in threads.into_iter().enumerate() {
+		assert_eq!(thread.join().unwrap(), format!("{}\n", i).repeat(20));
+	}
+	python! {
+		import sys
+		assert type(sys.stdout).__name__ != "RustWriter"
+	}
+}
+
+#[test]
+fn stdin_in_threads() {
+	// Reading releases the GIL, so the threads read at the same time.
+	let threads: Vec<_> = (0..4)
+		.map(|i| {
+			std::thread::spawn(move || {
+				let c = Context::new();
+				c.set_stdin(Trickle(std::io::Cursor::new(format!("{}\n", i).repeat(20))));
+				c.run(python! {
+					import sys
+					lines = sys.stdin.readlines()
+				});
+				c.get::<Vec<String>>("lines")
+			})
+		})
+		.collect();
+	for (i, thread) in threads.into_iter().enumerate() {
+		assert_eq!(thread.join().unwrap(), vec![format!("{}\n", i); 20]);
+	}
+}
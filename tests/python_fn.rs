@@ -0,0 +1,41 @@
+use inline_python::python_fn;
+use std::collections::HashMap;
+
+#[python_fn]
+fn add(a: i64, b: i64) -> i64 {
+	return a + b;
+}
+
+#[python_fn]
+fn count_words(text: &str) -> HashMap<String, usize> {
+	python! {
+		counts = {}
+		for word in text.split():
+			counts[word] = counts.get(word, 0) + 1
+		counts
+	}
+}
+
+#[python_fn]
+pub(crate) fn check(n: i32, offset: i32) {
+	python! {
+		n = n + 'offset
+		assert n == 5
+	}
+}
+
+#[test]
+fn python_functions() {
+	assert_eq!(add(1, 2), 3);
+	assert_eq!(add(40, 2), 42);
+	let counts = count_words("a b a");
+	assert_eq!(counts["a"], 2);
+	assert_eq!(counts["b"], 1);
+	check(3, 2);
+}
+
+#[test]
+#[should_panic]
+fn python_function_panics() {
+	check(1, 1);
+}
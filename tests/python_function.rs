@@ -0,0 +1,54 @@
+use inline_python::pyo3::PyObject;
+use inline_python::{python, Context};
+
+#[test]
+fn call_function() {
+	let c = Context::new();
+	c.run(python! {
+		def score(row, weights, *, bonus=0):
+			return sum(x * w for x, w in zip(row, weights)) + bonus
+	});
+	let score = c.function::<(Vec<i32>, Vec<f64>), f64>("score").unwrap();
+	assert_eq!(score.call((vec![1, 2], vec![0.5, 2.0])).unwrap(), 4.5);
+	assert_eq!(score.call_with_kwargs((vec![1], vec![1.0]), [("bonus", 1)]).unwrap(), 2.0);
+	assert_eq!(score.clone().call((vec![], vec![])).unwrap(), 0.0);
+}
+
+#[test]
+fn function_errors() {
+	let c = Context::new();
+	c.run(python! {
+		def one(x):
+			return str(x)
+		def three(a, b, c, *, key):
+			pass
+		not_a_function = 5
+	});
+
+	let error = c.function::<(i32, i32), String>("one").err().unwrap();
+	assert_eq!(error.type_name(), "TypeError");
+	assert!(error.message().starts_with("'one' can not be called with 2 positional arguments"));
+
+	let error = c.function::<(i32,), PyObject>("three").err().unwrap();
+	assert_eq!(error.type_name(), "TypeError");
+	assert_eq!(
+		error.message(),
+		"'three' can not be called with 1 positional argument: missing a required argument: 'b'"
+	);
+	// Keyword-only parameters can be given as keyword arguments.
+	assert!(c.function::<(i32, i32, i32), PyObject>("three").is_ok());
+
+	let error = c.function::<(), PyObject>("not_a_function").err().unwrap();
+	assert_eq!(error.message(), "'not_a_function' is not callable");
+
+	let error = c.function::<(), PyObject>("missing").err().unwrap();
+	assert_eq!(error.type_name(), "NameError");
+
+	let one = c.function::<(i32,), i32>("one").unwrap();
+	let error = one.call((1,)).unwrap_err();
+	assert_eq!(error.type_name(), "TypeError");
+	assert!(error.message().starts_with("unable to convert the result of 'one' to `i32`"));
+
+	let error = one.call_with_kwargs((1,), [("y", 2)]).unwrap_err();
+	assert_eq!(error.type_name(), "TypeError");
+}
@@ -0,0 +1,100 @@
+use inline_python::{python, Context, Serde};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+	Empty,
+	Circle(f64),
+	Rect { w: f64, h: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Scene {
+	name: String,
+	shapes: Vec<Shape>,
+	origin: (i32, i32),
+	tags: BTreeMap<String, u64>,
+	parent: Option<String>,
+}
+
+fn scene() -> Scene {
+	Scene {
+		name: "test".into(),
+		shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect { w: 2.0, h: 3.0 }],
+		origin: (-1, 2),
+		tags: vec![("a".to_string(), 1), ("b".to_string(), u64::MAX)].into_iter().collect(),
+		parent: None,
+	}
+}
+
+#[test]
+fn set_and_get() {
+	let c = Context::new();
+	c.set_serde("scene", &scene());
+	c.run(python! {
+		assert scene == {
+			"name": "test",
+			"shapes": ["Empty", {"Circle": 1.5}, {"Rect": {"w": 2.0, "h": 3.0}}],
+			"origin": (-1, 2),
+			"tags": {"a": 1, "b": 2**64 - 1},
+			"parent": None,
+		}
+		scene["parent"] = "root"
+	});
+	let scene: Scene = c.get_serde("scene");
+	assert_eq!(scene.parent.as_deref(), Some("root"));
+	assert_eq!(scene.shapes, self::scene().shapes);
+	assert_eq!(scene.tags, self::scene().tags);
+}
+
+#[test]
+fn interpolation() {
+	let scene = scene();
+	let shapes = &scene.shapes;
+	let n: usize = python! {
+		assert 'serde scene["origin"] == (-1, 2)
+		len('serde shapes)
+	};
+	assert_eq!(n, 3);
+
+	let Serde(shape): Serde<Shape> = python! {
+		{"Rect": {"w": 1, "h": 2.5}}
+	};
+	assert_eq!(shape, Shape::Rect { w: 1.0, h: 2.5 });
+}
+
+#[test]
+fn errors() {
+	let c: Context = python! {
+		a = {"name": "x"}
+		b = {"Triangle": 1}
+	};
+	let error = c.try_get_serde::<Scene>("a").unwrap_err();
+	assert_eq!(error.type_name(), "ValueError");
+	assert!(error.message().contains("missing field `shapes`"), "{}", error.message());
+	let error = c.try_get_serde::<Shape>("b").unwrap_err();
+	assert!(error.message().contains("unknown variant `Triangle`"), "{}", error.message());
+	assert_eq!(c.try_get_serde::<Shape>("c").unwrap_err().type_name(), "NameError");
+}
+
+struct Unserializable;
+
+impl Serialize for Unserializable {
+	fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+		Err(serde::ser::Error::custom("not today"))
+	}
+}
+
+#[test]
+fn interpolation_error() {
+	let c = Context::new();
+	let value = Unserializable;
+	let error = c
+		.try_run(python! {
+			x = 'serde value
+		})
+		.unwrap_err();
+	assert_eq!(error.type_name(), "ValueError");
+	assert_eq!(error.message(), "not today");
+}
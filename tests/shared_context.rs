@@ -21,3 +21,61 @@ fn extract_global() {
 
 	assert_eq!(c.get::<i32>("foo"), 5);
 }
+
+#[test]
+fn fallible_run() {
+	let c = inline_python::Context::new();
+
+	let error = c
+		.try_run(python! {
+			def fail():
+				raise KeyError("missing")
+			fail()
+		})
+		.unwrap_err();
+
+	assert_eq!(error.type_name(), "KeyError");
+	assert_eq!(error.message(), "'missing'");
+	assert!(error.traceback().unwrap().contains("in fail"));
+	assert!(c.try_get::<String>("fail").is_err());
+}
+
+#[test]
+fn block_result() {
+	let n: i32 = python! {
+		x = 5
+		x * 2
+	};
+	assert_eq!(n, 10);
+
+	let names: Vec<String> = python! {
+		names = []
+		for name in ["a", "b", "c"]:
+			if name == "c":
+				return names
+			names.append(name)
+	};
+	assert_eq!(names, ["a", "b"]);
+
+	let nothing: Option<i32> = python! {
+		x = 5
+	};
+	assert_eq!(nothing, None);
+}
+
+#[test]
+fn reuse_code_object() {
+	let c = inline_python::Context::new();
+	c.run(python! {
+		import inspect
+		codes = []
+	});
+	for _ in 0..3 {
+		c.run(python! {
+			codes.append(inspect.currentframe().f_code)
+		});
+	}
+	c.run(python! {
+		assert codes[0] is codes[1] is codes[2]
+	});
+}
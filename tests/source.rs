@@ -0,0 +1,59 @@
+use inline_python::Context;
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn run_source() {
+	let c = Context::new();
+	c.set("x", 4);
+	c.run_source("y = x * 2", "<test>");
+	assert_eq!(c.get::<i32>("y"), 8);
+	assert_eq!(c.eval_source::<i32>("y + 1", "<test>"), 9);
+	assert_eq!(c.try_eval_source::<i32>("y + 2", "<test>").unwrap(), 10);
+
+	let error = c.try_run_source("\n\nraise ValueError('oops')", "plugin.py").unwrap_err();
+	assert_eq!(error.type_name(), "ValueError");
+	assert!(error.traceback().unwrap().contains("File \"plugin.py\", line 3"));
+}
+
+#[test]
+fn plain_python_semantics() {
+	let c = Context::new();
+	let error = c.try_run_source("x = 1\nif x:\n\treturn\n", "<test>").unwrap_err();
+	assert_eq!(error.type_name(), "SyntaxError");
+	let error = c.try_run_source("import asyncio\nawait asyncio.sleep(0)\n", "<test>").unwrap_err();
+	assert_eq!(error.type_name(), "SyntaxError");
+
+	// Assignments in a script are global variables of the context.
+	c.run_source("def f():\n\treturn 1\ny = f()\n", "<test>");
+	assert_eq!(c.get::<i32>("y"), 1);
+}
+
+#[test]
+fn run_file() {
+	let path = std::env::temp_dir().join(format!("inline-python-test-{}.py", std::process::id()));
+	let c = Context::new();
+
+	std::fs::write(&path, "a = 1\na + 1").unwrap();
+	assert_eq!(c.eval_file::<i32>(&path), 2);
+	c.run_file(&path);
+	assert_eq!(c.get::<i32>("a"), 1);
+
+	// A modified file is compiled again.
+	let mut file = File::create(&path).unwrap();
+	file.write_all(b"a = 10\na + 1").unwrap();
+	file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+	drop(file);
+	assert_eq!(c.try_eval_file::<i32>(&path).unwrap(), 11);
+
+	std::fs::remove_file(&path).unwrap();
+	let error = c.try_run_file(&path).unwrap_err();
+	assert_eq!(error.type_name(), "FileNotFoundError");
+}
+
+#[test]
+#[should_panic(expected = "Python code in `<test>` failed to execute")]
+fn run_source_panics() {
+	Context::new().run_source("raise ValueError('oops')", "<test>");
+}
@@ -0,0 +1,31 @@
+use inline_python::{python, Context};
+
+#[test]
+fn verbatim_source() {
+	let x = 7;
+	let mut found: Vec<String> = vec![];
+	let result: i32 = python! {
+		#![verbatim]
+		# A comment with "quotes" and (parentheses).
+		half = 'x // 2
+		total = sum([1,   2,
+					 3])
+		'mut found.append(f"{half:>3}")
+		half * total + '_(x + 1)
+	};
+	assert_eq!(result, 26);
+	assert_eq!(found, ["  3"]);
+}
+
+#[test]
+fn verbatim_line_numbers() {
+	let c = Context::new();
+	let line = line!() + 5;
+	c.run(python! {
+		#![verbatim]
+		import inspect
+		# comment
+		lineno = inspect.currentframe().f_lineno
+	});
+	assert_eq!(c.get::<u32>("lineno"), line);
+}
@@ -0,0 +1,35 @@
+use inline_python::pyo3::types::PyDict;
+use inline_python::{EmbeddedCode, FromInlinePython};
+use std::sync::{Arc, Mutex};
+
+/// A block as if it was compiled by some other version of Python.
+static EMBEDDED: EmbeddedCode = EmbeddedCode {
+	bytecode: b"not valid bytecode",
+	magic: b"\0\0\r\n",
+	python_version: "2.7.18",
+	source: "x = 20\nimport inspect\nx + inspect.currentframe().f_lineno",
+	filename: "version_mismatch.rs",
+	line: 10,
+	parameters: None,
+};
+
+#[test]
+fn recompile_on_version_mismatch() {
+	let reported = Arc::new(Mutex::new(Vec::new()));
+	let r = reported.clone();
+	inline_python::on_version_mismatch(move |mismatch| {
+		r.lock().unwrap().push((
+			mismatch.filename().to_string(),
+			mismatch.line(),
+			mismatch.compiled_version().to_string(),
+		));
+	});
+
+	let result: i32 = FromInlinePython::from_python_macro(&EMBEDDED, |_: &PyDict| Ok(|_: &PyDict| Ok(())));
+	assert_eq!(result, 32);
+
+	assert_eq!(
+		*reported.lock().unwrap(),
+		[("version_mismatch.rs".to_string(), 10, "2.7.18".to_string())]
+	);
+}
@@ -0,0 +1,98 @@
+use inline_python::{python, Context, PyWarning};
+use std::sync::mpsc;
+
+fn collect_warnings(c: &Context) -> mpsc::Receiver<PyWarning> {
+	let (tx, rx) = mpsc::channel();
+	c.on_warning(move |warning| tx.send(warning).unwrap());
+	rx
+}
+
+#[test]
+fn on_warning() {
+	let c = Context::new();
+	let warnings = collect_warnings(&c);
+	let line = line!() + 4;
+	c.run(python! {
+		import warnings
+		for i in range(3):
+			warnings.warn("old", DeprecationWarning)
+		warnings.warn("careful")
+	});
+	let warnings: Vec<PyWarning> = warnings.try_iter().collect();
+	assert_eq!(warnings.len(), 2);
+	assert_eq!(warnings[0].category(), "DeprecationWarning");
+	assert_eq!(warnings[0].message(), "old");
+	assert_eq!(warnings[0].filename(), file!());
+	assert_eq!(warnings[0].line(), line as usize);
+	assert_eq!(warnings[1].category(), "UserWarning");
+	assert_eq!(warnings[1].to_string(), format!("{}:{}: UserWarning: careful", file!(), line + 1));
+}
+
+#[test]
+fn error_on_warning() {
+	let c = Context::new();
+	let warnings = collect_warnings(&c);
+	c.error_on_warning("DeprecationWarning");
+	c.run(python! {
+		import warnings
+		warnings.warn("not an error", RuntimeWarning)
+	});
+	assert_eq!(warnings.try_iter().count(), 1);
+	let error = c
+		.try_run(python! {
+			warnings.warn("removed soon", PendingDeprecationWarning)
+			warnings.warn("removed", DeprecationWarning)
+		})
+		.unwrap_err();
+	assert_eq!(error.type_name(), "DeprecationWarning");
+	assert_eq!(warnings.try_iter().count(), 1);
+
+	// Only while this context runs code.
+	python! {
+		import warnings
+		with warnings.catch_warnings(record=True) as caught:
+			warnings.warn("removed", DeprecationWarning)
+		assert [str(w.message) for w in caught] == ["removed"]
+	}
+	assert_eq!(warnings.try_iter().count(), 0);
+}
+
+#[test]
+fn warnings_in_threads() {
+	// The threads take turns holding the GIL while they sleep, so their settings must not mix.
+	let threads: Vec<_> = (0..4)
+		.map(|i| {
+			std::thread::spawn(move || {
+				let c = Context::new();
+				let warnings = collect_warnings(&c);
+				if i % 2 == 0 {
+					c.error_on_warning("UserWarning");
+				}
+				c.run(python! {
+					import time
+					import warnings
+					errors = 0
+					for n in range(20):
+						try:
+							warnings.warn(f"{n}")
+						except UserWarning:
+							errors += 1
+						time.sleep(0.001)
+				});
+				(c.get::<usize>("errors"), warnings.try_iter().count())
+			})
+		})
+		.collect();
+	for (i, thread) in threads.into_iter().enumerate() {
+		let expected = if i % 2 == 0 { (20, 0) } else { (0, 20) };
+		assert_eq!(thread.join().unwrap(), expected);
+	}
+}
+
+#[test]
+fn unknown_category() {
+	let c = Context::new();
+	c.error_on_warning("no_such_module.SomeWarning");
+	let error = c.try_run(python! {}).unwrap_err();
+	assert_eq!(error.type_name(), "ModuleNotFoundError");
+}